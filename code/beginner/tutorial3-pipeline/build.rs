@@ -0,0 +1,9 @@
+// Compiles the glsl shaders to spirv, so we don't need a shader compiler at runtime
+fn main() {
+    framework::build::compile_shaders(&["src"]);
+    // The challenge's second pipeline uses the same shaders with vertex colors turned on
+    framework::build::compile_shader_variants(&[
+        ("src/shader.vert", &["USE_VERTEX_COLOR"]),
+        ("src/shader.frag", &["USE_VERTEX_COLOR"]),
+    ]);
+}
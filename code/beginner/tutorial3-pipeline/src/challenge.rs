@@ -3,6 +3,67 @@ use winit::{
     event_loop::{EventLoop, ControlFlow},
     window::{Window, WindowBuilder},
 };
+use framework::preprocessor::Defines;
+use framework::shader::ShaderStage;
+use framework::variants::{PipelineVariants, ShaderVariants, Variant, VariantKey};
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+struct PipelineKey {
+    vertex_color: bool,
+}
+
+impl VariantKey for PipelineKey {
+    fn defines(&self) -> Defines {
+        let mut defines = Defines::new();
+        if self.vertex_color {
+            defines = defines.with("USE_VERTEX_COLOR");
+        }
+        defines
+    }
+}
+
+fn create_render_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    format: wgpu::TextureFormat,
+    vs: &Variant,
+    fs: &Variant,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        layout,
+        vertex_stage: wgpu::ProgrammableStageDescriptor {
+            module: &vs.module,
+            entry_point: "main",
+        },
+        fragment_stage: Some(wgpu::ProgrammableStageDescriptor {
+            module: &fs.module,
+            entry_point: "main",
+        }),
+        rasterization_state: Some(wgpu::RasterizationStateDescriptor {
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: wgpu::CullMode::Back,
+            depth_bias: 0,
+            depth_bias_slope_scale: 0.0,
+            depth_bias_clamp: 0.0,
+        }),
+        primitive_topology: wgpu::PrimitiveTopology::TriangleList,
+        color_states: &[
+            wgpu::ColorStateDescriptor {
+                format,
+                color_blend: wgpu::BlendDescriptor::REPLACE,
+                alpha_blend: wgpu::BlendDescriptor::REPLACE,
+                write_mask: wgpu::ColorWrite::ALL,
+            },
+        ],
+        depth_stencil_state: None,
+        // Only used by indexed draws, and this doesn't have an index buffer
+        index_format: wgpu::IndexFormat::Uint16,
+        vertex_buffers: &[],
+        sample_count: 1,
+        sample_mask: !0,
+        alpha_to_coverage_enabled: false,
+    })
+}
 
 struct State {
     surface: wgpu::Surface,
@@ -11,8 +72,7 @@ struct State {
     sc_desc: wgpu::SwapChainDescriptor,
     swap_chain: wgpu::SwapChain,
 
-    render_pipeline: wgpu::RenderPipeline,
-    challenge_render_pipeline: wgpu::RenderPipeline,
+    render_pipelines: PipelineVariants<PipelineKey>,
     use_color: bool,
 
     hidpi_factor: f64,
@@ -47,99 +107,28 @@ impl State {
         };
         let swap_chain = device.create_swap_chain(&surface, &sc_desc);
 
-        let vs_src = include_str!("shader.vert");
-        let fs_src = include_str!("shader.frag");
-        let vs_spirv = glsl_to_spirv::compile(vs_src, glsl_to_spirv::ShaderType::Vertex).unwrap();
-        let fs_spirv = glsl_to_spirv::compile(fs_src, glsl_to_spirv::ShaderType::Fragment).unwrap();
-        let vs_data = wgpu::read_spirv(vs_spirv).unwrap();
-        let fs_data = wgpu::read_spirv(fs_spirv).unwrap();
-        let vs_module = device.create_shader_module(&vs_data);
-        let fs_module = device.create_shader_module(&fs_data);
-
         let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             bind_group_layouts: &[],
         });
 
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            layout: &render_pipeline_layout,
-            vertex_stage: wgpu::ProgrammableStageDescriptor {
-                module: &vs_module,
-                entry_point: "main",
-            },
-            fragment_stage: Some(wgpu::ProgrammableStageDescriptor {
-                module: &fs_module,
-                entry_point: "main",
-            }),
-            rasterization_state: Some(wgpu::RasterizationStateDescriptor {
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: wgpu::CullMode::Back,
-                depth_bias: 0,
-                depth_bias_slope_scale: 0.0,
-                depth_bias_clamp: 0.0,
-            }),
-            primitive_topology: wgpu::PrimitiveTopology::TriangleList,
-            color_states: &[
-                wgpu::ColorStateDescriptor {
-                    format: sc_desc.format,
-                    color_blend: wgpu::BlendDescriptor::REPLACE,
-                    alpha_blend: wgpu::BlendDescriptor::REPLACE,
-                    write_mask: wgpu::ColorWrite::ALL,
-                },
-            ],
-            depth_stencil_state: None,
-            index_format: wgpu::IndexFormat::Uint16,
-            vertex_buffers: &[],
-            sample_count: 1,
-            sample_mask: !0,
-            alpha_to_coverage_enabled: false,
-        });
-
-        let vs_src = include_str!("challenge.vert");
-        let fs_src = include_str!("challenge.frag");
-        let vs_spirv = glsl_to_spirv::compile(vs_src, glsl_to_spirv::ShaderType::Vertex).unwrap();
-        let fs_spirv = glsl_to_spirv::compile(fs_src, glsl_to_spirv::ShaderType::Fragment).unwrap();
-        let vs_data = wgpu::read_spirv(vs_spirv).unwrap();
-        let fs_data = wgpu::read_spirv(fs_spirv).unwrap();
-        let vs_module = device.create_shader_module(&vs_data);
-        let fs_module = device.create_shader_module(&fs_data);
-
-        let challenge_render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            bind_group_layouts: &[],
-        });
+        // Both pipelines come from the same two shaders. The challenge version
+        // is compiled with USE_VERTEX_COLOR defined, and build.rs compiles both.
+        let vertex = ShaderVariants::embedded("shader.vert", ShaderStage::Vertex, &[
+            (&[], framework::include_spirv!("src/shader.vert")),
+            (&["USE_VERTEX_COLOR"], framework::include_spirv!("src/shader.vert", "USE_VERTEX_COLOR")),
+        ]);
+        let fragment = ShaderVariants::embedded("shader.frag", ShaderStage::Fragment, &[
+            (&[], framework::include_spirv!("src/shader.frag")),
+            (&["USE_VERTEX_COLOR"], framework::include_spirv!("src/shader.frag", "USE_VERTEX_COLOR")),
+        ]);
 
-        let challenge_render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            layout: &challenge_render_pipeline_layout,
-            vertex_stage: wgpu::ProgrammableStageDescriptor {
-                module: &vs_module,
-                entry_point: "main",
-            },
-            fragment_stage: Some(wgpu::ProgrammableStageDescriptor {
-                module: &fs_module,
-                entry_point: "main",
-            }),
-            rasterization_state: Some(wgpu::RasterizationStateDescriptor {
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: wgpu::CullMode::Back,
-                depth_bias: 0,
-                depth_bias_slope_scale: 0.0,
-                depth_bias_clamp: 0.0,
-            }),
-            primitive_topology: wgpu::PrimitiveTopology::TriangleList,
-            color_states: &[
-                wgpu::ColorStateDescriptor {
-                    format: sc_desc.format,
-                    color_blend: wgpu::BlendDescriptor::REPLACE,
-                    alpha_blend: wgpu::BlendDescriptor::REPLACE,
-                    write_mask: wgpu::ColorWrite::ALL,
-                },
-            ],
-            depth_stencil_state: None,
-            index_format: wgpu::IndexFormat::Uint16,
-            vertex_buffers: &[],
-            sample_count: 1,
-            sample_mask: !0,
-            alpha_to_coverage_enabled: false,
-        });
+        // We build both variants now, so switching between them is instant
+        let mut render_pipelines = PipelineVariants::new(vertex, fragment);
+        for &vertex_color in &[false, true] {
+            render_pipelines.prepare(&device, &PipelineKey { vertex_color }, |vs, fs| {
+                create_render_pipeline(&device, &render_pipeline_layout, sc_desc.format, vs, fs)
+            }).unwrap();
+        }
 
         let use_color = true;
 
@@ -149,8 +138,7 @@ impl State {
             queue,
             sc_desc,
             swap_chain,
-            render_pipeline,
-            challenge_render_pipeline,
+            render_pipelines,
             use_color,
             hidpi_factor,
             size,
@@ -219,11 +207,8 @@ impl State {
                 depth_stencil_attachment: None,
             });
 
-            render_pass.set_pipeline(if self.use_color {
-                &self.render_pipeline
-            } else {
-                &self.challenge_render_pipeline
-            });
+            let key = PipelineKey { vertex_color: !self.use_color };
+            render_pass.set_pipeline(self.render_pipelines.get(&key).unwrap());
             render_pass.draw(0..3, 0..1);
         }
 
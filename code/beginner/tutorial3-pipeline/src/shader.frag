@@ -1,7 +1,14 @@
 #version 450
 
+#ifdef USE_VERTEX_COLOR
+layout(location=0) in vec2 v_position;
+#endif
 layout(location=0) out vec4 f_color;
 
 void main() {
+#ifdef USE_VERTEX_COLOR
+    f_color = vec4(v_position, 0.5, 1.0);
+#else
     f_color = vec4(0.3, 0.2, 0.1, 1.0);
+#endif
 }
\ No newline at end of file
@@ -6,6 +6,13 @@ const vec2 positions[3] = vec2[3](
     vec2(0.5, 0.5)
 );
 
+#ifdef USE_VERTEX_COLOR
+layout(location=0) out vec2 v_position;
+#endif
+
 void main() {
+#ifdef USE_VERTEX_COLOR
+    v_position = positions[gl_VertexIndex];
+#endif
     gl_Position = vec4(positions[gl_VertexIndex], 0.0, 1.0);
 }
\ No newline at end of file
@@ -0,0 +1,5 @@
+// Compiles the shaders to spirv, so we don't need a shader compiler at runtime.
+// examples/ has the tutorial's shaders written in wgsl.
+fn main() {
+    framework::build::compile_shaders(&["src", "examples"]);
+}
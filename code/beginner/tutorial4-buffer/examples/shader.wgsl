@@ -0,0 +1,22 @@
+// Both stages live in this one file. build.rs compiles it with naga, and each
+// stage's entry point is its own `main`.
+
+[[location(0)]] var<in> a_position: vec3<f32>;
+[[location(1)]] var<in> a_color: vec3<f32>;
+
+[[location(0)]] var<out> v_color: vec3<f32>;
+[[builtin(position)]] var<out> out_position: vec4<f32>;
+
+[[stage(vertex)]]
+fn main() {
+    v_color = a_color;
+    out_position = vec4<f32>(a_position, 1.0);
+}
+
+[[location(0)]] var<in> v_color: vec3<f32>;
+[[location(0)]] var<out> f_color: vec4<f32>;
+
+[[stage(fragment)]]
+fn main() {
+    f_color = vec4<f32>(v_color, 1.0);
+}
@@ -3,6 +3,8 @@ use winit::{
     event_loop::{EventLoop, ControlFlow},
     window::{Window, WindowBuilder},
 };
+use framework::shader::ShaderStage;
+use framework::validate::{validate, ValidationDescriptor, ViewedFrom};
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug)]
@@ -97,14 +99,11 @@ impl State {
         };
         let swap_chain = device.create_swap_chain(&surface, &sc_desc);
 
-        let vs_src = include_str!("shader.vert");
-        let fs_src = include_str!("shader.frag");
-        let vs_spirv = glsl_to_spirv::compile(vs_src, glsl_to_spirv::ShaderType::Vertex).unwrap();
-        let fs_spirv = glsl_to_spirv::compile(fs_src, glsl_to_spirv::ShaderType::Fragment).unwrap();
-        let vs_data = wgpu::read_spirv(vs_spirv).unwrap();
-        let fs_data = wgpu::read_spirv(fs_spirv).unwrap();
-        let vs_module = device.create_shader_module(&vs_data);
-        let fs_module = device.create_shader_module(&fs_data);
+        // The shaders are compiled to spirv by build.rs
+        let vs_spirv = framework::include_spirv!("src/shader.vert");
+        let fs_spirv = framework::include_spirv!("src/shader.frag");
+        let vs_module = framework::shader::create_spirv_module(&device, "shader.vert", ShaderStage::Vertex, vs_spirv).unwrap();
+        let fs_module = framework::shader::create_spirv_module(&device, "shader.frag", ShaderStage::Fragment, fs_spirv).unwrap();
 
         let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             bind_group_layouts: &[],
@@ -178,6 +177,23 @@ impl State {
         }).collect::<Vec<_>>();
         let num_challenge_indices = challenge_indices.len() as u32;
 
+        // Wrong winding doesn't cause an error, the triangles just get culled,
+        // so check both shapes against the pipeline before uploading them.
+        // Clip space is seen from -z.
+        let validation = ValidationDescriptor {
+            primitive_topology: wgpu::PrimitiveTopology::TriangleList,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: wgpu::CullMode::Back,
+            viewed_from: Some(ViewedFrom::Direction([0.0, 0.0, -1.0])),
+        };
+        let reports = [
+            validate(VERTICES, |v| v.position, INDICES, &validation),
+            validate(&challenge_verts, |v| v.position, &challenge_indices, &validation),
+        ];
+        for report in reports.iter().filter(|report| !report.is_ok()) {
+            eprintln!("{}", report);
+        }
+
         let challenge_vertex_buffer = device
             .create_buffer_mapped(challenge_verts.len(), wgpu::BufferUsage::VERTEX)
             .fill_from_slice(&challenge_verts);
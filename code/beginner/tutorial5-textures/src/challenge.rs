@@ -3,6 +3,7 @@ use winit::{
     event_loop::{EventLoop, ControlFlow},
     window::{Window, WindowBuilder},
 };
+use framework::shader::ShaderStage;
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug)]
@@ -229,14 +230,11 @@ impl State {
             cartoon_bytes,
         );
 
-        let vs_src = include_str!("shader.vert");
-        let fs_src = include_str!("shader.frag");
-        let vs_spirv = glsl_to_spirv::compile(vs_src, glsl_to_spirv::ShaderType::Vertex).unwrap();
-        let fs_spirv = glsl_to_spirv::compile(fs_src, glsl_to_spirv::ShaderType::Fragment).unwrap();
-        let vs_data = wgpu::read_spirv(vs_spirv).unwrap();
-        let fs_data = wgpu::read_spirv(fs_spirv).unwrap();
-        let vs_module = device.create_shader_module(&vs_data);
-        let fs_module = device.create_shader_module(&fs_data);
+        // The shaders are compiled to spirv by build.rs
+        let vs_spirv = framework::include_spirv!("src/shader.vert");
+        let fs_spirv = framework::include_spirv!("src/shader.frag");
+        let vs_module = framework::shader::create_spirv_module(&device, "shader.vert", ShaderStage::Vertex, vs_spirv).unwrap();
+        let fs_module = framework::shader::create_spirv_module(&device, "shader.frag", ShaderStage::Fragment, fs_spirv).unwrap();
 
         let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             bind_group_layouts: &[&texture_bind_group_layout],
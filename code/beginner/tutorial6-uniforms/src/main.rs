@@ -3,6 +3,10 @@ use winit::{
     event_loop::{EventLoop, ControlFlow},
     window::{Window, WindowBuilder},
 };
+use framework::mesh::{Indices, Mesh};
+use framework::shader::ShaderStage;
+use framework::pod::Pod;
+use framework::staging::StagingRing;
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug)]
@@ -41,7 +45,7 @@ const VERTICES: &[Vertex] = &[
     Vertex { position: [0.44147372, -0.2347359, 0.0], tex_coords: [1.0 - 0.9414737, 1.0 - 0.2652641], }, // E
 ];
 
-const INDICES: &[u16] = &[
+const INDICES: &[u32] = &[
     0, 1, 4,
     1, 2, 4,
     2, 3, 4,
@@ -79,6 +83,9 @@ struct Uniforms {
     view_proj: cgmath::Matrix4<f32>,
 }
 
+// Safe because a matrix of f32s is all `Uniforms` holds, so there's no padding
+unsafe impl Pod for Uniforms {}
+
 impl Uniforms {
     fn new() -> Self {
         use cgmath::SquareMatrix;
@@ -189,9 +196,7 @@ struct State {
 
     render_pipeline: wgpu::RenderPipeline,
 
-    vertex_buffer: wgpu::Buffer,
-    index_buffer: wgpu::Buffer,
-    num_indices: u32,
+    mesh: Mesh,
 
     diffuse_texture: wgpu::Texture,
     diffuse_texture_view: wgpu::TextureView,
@@ -203,6 +208,7 @@ struct State {
     uniforms: Uniforms,
     uniform_buffer: wgpu::Buffer,
     uniform_bind_group: wgpu::BindGroup,
+    staging_ring: StagingRing,
 
     hidpi_factor: f64,
     size: winit::dpi::LogicalSize,
@@ -372,19 +378,22 @@ impl State {
             ],
         });
 
-        let vs_src = include_str!("shader.vert");
-        let fs_src = include_str!("shader.frag");
-        let vs_spirv = glsl_to_spirv::compile(vs_src, glsl_to_spirv::ShaderType::Vertex).unwrap();
-        let fs_spirv = glsl_to_spirv::compile(fs_src, glsl_to_spirv::ShaderType::Fragment).unwrap();
-        let vs_data = wgpu::read_spirv(vs_spirv).unwrap();
-        let fs_data = wgpu::read_spirv(fs_spirv).unwrap();
-        let vs_module = device.create_shader_module(&vs_data);
-        let fs_module = device.create_shader_module(&fs_data);
+        // Uploads the uniforms each frame. A chunk fits one frame's worth, and
+        // another gets made whenever the gpu is still reading the last one.
+        let staging_ring = StagingRing::new(std::mem::size_of::<Uniforms>() as wgpu::BufferAddress);
+
+        // The shaders are compiled to spirv by build.rs
+        let vs_spirv = framework::include_spirv!("src/shader.vert");
+        let fs_spirv = framework::include_spirv!("src/shader.frag");
+        let vs_module = framework::shader::create_spirv_module(&device, "shader.vert", ShaderStage::Vertex, vs_spirv).unwrap();
+        let fs_module = framework::shader::create_spirv_module(&device, "shader.frag", ShaderStage::Fragment, fs_spirv).unwrap();
 
         let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             bind_group_layouts: &[&texture_bind_group_layout, &uniform_bind_group_layout],
         });
 
+        let mesh = Mesh::new(&device, VERTICES, &Indices::new(INDICES, VERTICES.len()));
+
         let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
             layout: &render_pipeline_layout,
             vertex_stage: wgpu::ProgrammableStageDescriptor {
@@ -412,7 +421,7 @@ impl State {
                 },
             ],
             depth_stencil_state: None,
-            index_format: wgpu::IndexFormat::Uint16,
+            index_format: mesh.index_format,
             vertex_buffers: &[
                 Vertex::desc(),
             ],
@@ -421,14 +430,6 @@ impl State {
             alpha_to_coverage_enabled: false,
         });
 
-        let vertex_buffer = device
-            .create_buffer_mapped(VERTICES.len(), wgpu::BufferUsage::VERTEX)
-            .fill_from_slice(VERTICES);
-        let index_buffer = device
-            .create_buffer_mapped(INDICES.len(), wgpu::BufferUsage::INDEX)
-            .fill_from_slice(INDICES);
-        let num_indices = INDICES.len() as u32;
-
         Self {
             surface,
             device,
@@ -436,9 +437,7 @@ impl State {
             sc_desc,
             swap_chain,
             render_pipeline,
-            vertex_buffer,
-            index_buffer,
-            num_indices,
+            mesh,
             diffuse_texture,
             diffuse_texture_view,
             diffuse_sampler,
@@ -448,6 +447,7 @@ impl State {
             uniform_buffer,
             uniform_bind_group,
             uniforms,
+            staging_ring,
             hidpi_factor,
             size,
         }
@@ -469,7 +469,21 @@ impl State {
     }
 
     fn input(&mut self, event: &WindowEvent) -> bool {
-        self.camera_controller.process_events(event)
+        match event {
+            // Prints how much of the staging memory the last frame used
+            WindowEvent::KeyboardInput {
+                input: KeyboardInput {
+                    state: ElementState::Pressed,
+                    virtual_keycode: Some(VirtualKeyCode::U),
+                    ..
+                },
+                ..
+            } => {
+                println!("staging: {}", self.staging_ring.usage());
+                true
+            }
+            _ => self.camera_controller.process_events(event),
+        }
     }
 
     fn update(&mut self) {
@@ -480,13 +494,13 @@ impl State {
             todo: 0,
         });
 
-        let staging_buffer = self.device
-            .create_buffer_mapped(1, wgpu::BufferUsage::COPY_SRC)
-            .fill_from_slice(&[self.uniforms]);
-
-        encoder.copy_buffer_to_buffer(&staging_buffer, 0, &self.uniform_buffer, 0, std::mem::size_of::<Uniforms>() as wgpu::BufferAddress);
+        // Reuses the same staging memory every frame instead of making a new
+        // buffer for each upload
+        self.staging_ring.write(&self.device, &mut encoder, &self.uniform_buffer, 0, &[self.uniforms]);
+        self.staging_ring.finish();
 
         self.queue.submit(&[encoder.finish()]);
+        self.staging_ring.recall(&self.device);
     }
 
     fn render(&mut self) {
@@ -518,9 +532,9 @@ impl State {
             render_pass.set_pipeline(&self.render_pipeline);
             render_pass.set_bind_group(0, &self.diffuse_bind_group, &[]);
             render_pass.set_bind_group(1, &self.uniform_bind_group, &[]);
-            render_pass.set_vertex_buffers(0, &[(&self.vertex_buffer, 0)]);
-            render_pass.set_index_buffer(&self.index_buffer, 0);
-            render_pass.draw_indexed(0..self.num_indices, 0, 0..1);
+            render_pass.set_vertex_buffers(0, &[(&self.mesh.vertex_buffer, 0)]);
+            render_pass.set_index_buffer(&self.mesh.index_buffer, 0);
+            render_pass.draw_indexed(0..self.mesh.num_indices, 0, 0..1);
         }
 
         self.queue.submit(&[
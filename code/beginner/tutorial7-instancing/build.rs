@@ -0,0 +1,14 @@
+// Compiles the glsl shaders to spirv, so we don't need a shader compiler at runtime
+fn main() {
+    framework::build::compile_shaders(&["src"]);
+    // The examples share one vertex shader, with a variant for each way of
+    // getting the instances to it
+    framework::build::compile_shader_variants(&[
+        ("examples/shader.frag", &[]),
+        ("examples/instancing.vert", &["INSTANCING_NAIVE"]),
+        ("examples/instancing.vert", &["INSTANCING_ARRAYS"]),
+        ("examples/instancing.vert", &["INSTANCING_STORAGE"]),
+        ("examples/instancing.vert", &["INSTANCING_TEXTURE"]),
+        ("examples/instancing.vert", &["INSTANCING_VERTEX"]),
+    ]);
+}
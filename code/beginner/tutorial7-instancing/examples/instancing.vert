@@ -0,0 +1,78 @@
+#version 450
+
+#include "../src/common/vertex.glsl"
+
+layout(location=0) out vec2 v_tex_coords;
+
+// Each example gets the instances' model matrices into the shader a different
+// way, and build.rs compiles a variant of this shader for each of them.
+// `get_model` hides the difference from `main`.
+
+#ifdef INSTANCING_NAIVE
+layout(set=1, binding=0) 
+uniform Uniforms {
+    mat4 u_view_proj;
+    mat4 u_model;
+};
+
+mat4 get_model() {
+    return u_model;
+}
+#endif
+
+#ifdef INSTANCING_ARRAYS
+layout(set=1, binding=0) 
+uniform Uniforms {
+    mat4 u_view_proj;
+    mat4 u_model[100];
+};
+
+mat4 get_model() {
+    return u_model[gl_InstanceIndex];
+}
+#endif
+
+#ifdef INSTANCING_STORAGE
+#include "../src/common/camera.glsl"
+
+layout(set=1, binding=1) 
+readonly buffer Instances {
+    mat4 s_models[];
+};
+
+mat4 get_model() {
+    return s_models[gl_InstanceIndex];
+}
+#endif
+
+#ifdef INSTANCING_TEXTURE
+#include "../src/common/camera.glsl"
+
+layout(set = 1, binding = 1) uniform texture1D t_model;
+layout(set = 1, binding = 2) uniform sampler s_model;
+
+mat4 get_model() {
+    int index = gl_InstanceIndex;
+    return mat4(
+        texelFetch(sampler1D(t_model, s_model), index * 4, 0),
+        texelFetch(sampler1D(t_model, s_model), index * 4 + 1, 0),
+        texelFetch(sampler1D(t_model, s_model), index * 4 + 2, 0),
+        texelFetch(sampler1D(t_model, s_model), index * 4 + 3, 0)
+    );
+}
+#endif
+
+#ifdef INSTANCING_VERTEX
+layout(location=2) in mat4 a_model;
+
+#include "../src/common/camera.glsl"
+
+mat4 get_model() {
+    return a_model;
+}
+#endif
+
+void main() {
+    v_tex_coords = a_tex_coords;
+    gl_Position = u_view_proj * get_model() * vec4(a_position, 1.0);
+}
@@ -0,0 +1,48 @@
+// Every example draws the same trees, and only changes how each tree's model
+// matrix gets to the vertex shader. examples/instancing.vert has an `#ifdef`
+// for each of those ways, and `Instancing` is the key that picks one.
+use framework::preprocessor::Defines;
+use framework::shader::ShaderStage;
+use framework::variants::{PipelineVariants, ShaderVariants, VariantKey};
+
+// Each example only uses one of these
+#[allow(dead_code)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Instancing {
+    // One draw call per instance, with the matrix in the uniform buffer
+    Naive,
+    // Every matrix in a uniform array, picked with gl_InstanceIndex
+    Arrays,
+    // Every matrix in a storage buffer
+    Storage,
+    // Every matrix in a 1d texture, four texels each
+    Texture,
+    // The matrices in a second vertex buffer that steps once per instance
+    Vertex,
+}
+
+impl VariantKey for Instancing {
+    fn defines(&self) -> Defines {
+        let define = match self {
+            Instancing::Naive => "INSTANCING_NAIVE",
+            Instancing::Arrays => "INSTANCING_ARRAYS",
+            Instancing::Storage => "INSTANCING_STORAGE",
+            Instancing::Texture => "INSTANCING_TEXTURE",
+            Instancing::Vertex => "INSTANCING_VERTEX",
+        };
+        Defines::new().with(define)
+    }
+}
+
+// The variants build.rs compiled, with the fragment shader they all share
+pub fn pipeline_variants() -> PipelineVariants<Instancing> {
+    let vertex = ShaderVariants::embedded("instancing.vert", ShaderStage::Vertex, &[
+        (&["INSTANCING_NAIVE"], framework::include_spirv!("examples/instancing.vert", "INSTANCING_NAIVE")),
+        (&["INSTANCING_ARRAYS"], framework::include_spirv!("examples/instancing.vert", "INSTANCING_ARRAYS")),
+        (&["INSTANCING_STORAGE"], framework::include_spirv!("examples/instancing.vert", "INSTANCING_STORAGE")),
+        (&["INSTANCING_TEXTURE"], framework::include_spirv!("examples/instancing.vert", "INSTANCING_TEXTURE")),
+        (&["INSTANCING_VERTEX"], framework::include_spirv!("examples/instancing.vert", "INSTANCING_VERTEX")),
+    ]);
+    let fragment = ShaderVariants::single("shader.frag", ShaderStage::Fragment, framework::include_spirv!("examples/shader.frag"));
+    PipelineVariants::new(vertex, fragment)
+}
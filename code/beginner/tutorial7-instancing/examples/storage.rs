@@ -3,37 +3,27 @@ use winit::{
     event_loop::{EventLoop, ControlFlow},
     window::{Window, WindowBuilder},
 };
+use framework::mesh::{Indices, Mesh};
+use framework::pod::Pod;
+use framework::shader::ShaderStage;
+use framework::staging::StagingRing;
+use framework::variants::PipelineVariants;
+use framework::vertex::VertexLayout;
 use cgmath::prelude::*;
 
+mod instancing;
+use instancing::Instancing;
+
+// Which variant of examples/instancing.vert this example uses
+const INSTANCING: Instancing = Instancing::Storage;
+
 #[repr(C)]
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, VertexLayout)]
 struct Vertex {
     position: [f32; 3],
     tex_coords: [f32; 2],
 }
 
-impl Vertex {
-    fn desc<'a>() -> wgpu::VertexBufferDescriptor<'a> {
-        use std::mem;
-        wgpu::VertexBufferDescriptor {
-            stride: mem::size_of::<Vertex>() as wgpu::BufferAddress,
-            step_mode: wgpu::InputStepMode::Vertex,
-            attributes: &[
-                wgpu::VertexAttributeDescriptor {
-                    offset: 0,
-                    shader_location: 0,
-                    format: wgpu::VertexFormat::Float3,
-                },
-                wgpu::VertexAttributeDescriptor {
-                    offset: mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
-                    shader_location: 1,
-                    format: wgpu::VertexFormat::Float2,
-                },
-            ]
-        }
-    }
-}
-
 const VERTICES: &[Vertex] = &[
     Vertex { position: [-0.0868241, -0.49240386, 0.0], tex_coords: [1.0 - 0.4131759, 1.0 - 0.00759614], }, // A
     Vertex { position: [-0.49513406, -0.06958647, 0.0], tex_coords: [1.0 - 0.0048659444, 1.0 - 0.43041354], }, // B
@@ -42,7 +32,7 @@ const VERTICES: &[Vertex] = &[
     Vertex { position: [0.44147372, -0.2347359, 0.0], tex_coords: [1.0 - 0.9414737, 1.0 - 0.2652641], }, // E
 ];
 
-const INDICES: &[u16] = &[
+const INDICES: &[u32] = &[
     0, 1, 4,
     1, 2, 4,
     2, 3, 4,
@@ -85,6 +75,9 @@ struct Uniforms {
     view_proj: cgmath::Matrix4<f32>,
 }
 
+// Safe because `Uniforms` is only f32 matrices, so there's no padding
+unsafe impl Pod for Uniforms {}
+
 impl Uniforms {
     fn new() -> Self {
         Self {
@@ -202,11 +195,9 @@ struct State {
     sc_desc: wgpu::SwapChainDescriptor,
     swap_chain: wgpu::SwapChain,
 
-    render_pipeline: wgpu::RenderPipeline,
+    render_pipelines: PipelineVariants<Instancing>,
 
-    vertex_buffer: wgpu::Buffer,
-    index_buffer: wgpu::Buffer,
-    num_indices: u32,
+    mesh: Mesh,
 
     diffuse_texture: wgpu::Texture,
     diffuse_texture_view: wgpu::TextureView,
@@ -218,6 +209,7 @@ struct State {
     uniforms: Uniforms,
     uniform_buffer: wgpu::Buffer,
     uniform_bind_group: wgpu::BindGroup,
+    staging_ring: StagingRing,
 
     hidpi_factor: f64,
     size: winit::dpi::LogicalSize,
@@ -315,26 +307,23 @@ impl State {
             compare_function: wgpu::CompareFunction::Always,
         });
 
-        let texture_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            bindings: &[
-                wgpu::BindGroupLayoutBinding {
-                    binding: 0,
-                    visibility: wgpu::ShaderStage::FRAGMENT,
-                    ty: wgpu::BindingType::SampledTexture {
-                        multisampled: false,
-                        dimension: wgpu::TextureViewDimension::D2,
-                    },
-                },
-                wgpu::BindGroupLayoutBinding {
-                    binding: 1,
-                    visibility: wgpu::ShaderStage::FRAGMENT,
-                    ty: wgpu::BindingType::Sampler,
-                },
-            ],
-        });
+        let mesh = Mesh::new(&device, VERTICES, &Indices::new(INDICES, VERTICES.len()));
+
+        // The shaders are compiled to spirv by build.rs
+        let mut render_pipelines = instancing::pipeline_variants();
+        let (vs, fs) = render_pipelines.shaders(&device, &INSTANCING).unwrap();
+
+        // Set 0 is the texture and sampler in shader.frag. Set 1 is the uniforms
+        // and the storage buffer with the instances in instancing.vert.
+        let vs_reflection = framework::reflect::reflect(&vs.spirv).unwrap();
+        let fs_reflection = framework::reflect::reflect(&fs.spirv).unwrap();
+        let layout = framework::layout::create_pipeline_layout(&device, &[
+            (ShaderStage::Vertex, &vs_reflection),
+            (ShaderStage::Fragment, &fs_reflection),
+        ]).unwrap();
 
         let diffuse_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &texture_bind_group_layout,
+            layout: &layout.bind_group_layouts[0],
             bindings: &[
                 wgpu::Binding {
                     binding: 0,
@@ -389,28 +378,8 @@ impl State {
             .create_buffer_mapped(instance_data.len(), wgpu::BufferUsage::STORAGE_READ)
             .fill_from_slice(&instance_data);
 
-        let uniform_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            bindings: &[
-                wgpu::BindGroupLayoutBinding {
-                    binding: 0,
-                    visibility: wgpu::ShaderStage::VERTEX,
-                    ty: wgpu::BindingType::UniformBuffer {
-                        dynamic: false,
-                    },
-                },
-                wgpu::BindGroupLayoutBinding {
-                    binding: 1,
-                    visibility: wgpu::ShaderStage::VERTEX,
-                    ty: wgpu::BindingType::StorageBuffer {
-                        dynamic: false,
-                        readonly: true,
-                    }
-                }
-            ]
-        });
-
         let uniform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &uniform_bind_group_layout,
+            layout: &layout.bind_group_layouts[1],
             bindings: &[
                 wgpu::Binding {
                     binding: 0,
@@ -429,62 +398,47 @@ impl State {
             ],
         });
 
-        let vs_src = include_str!("storage.vert");
-        let fs_src = include_str!("shader.frag");
-        let vs_spirv = glsl_to_spirv::compile(vs_src, glsl_to_spirv::ShaderType::Vertex).unwrap();
-        let fs_spirv = glsl_to_spirv::compile(fs_src, glsl_to_spirv::ShaderType::Fragment).unwrap();
-        let vs_data = wgpu::read_spirv(vs_spirv).unwrap();
-        let fs_data = wgpu::read_spirv(fs_spirv).unwrap();
-        let vs_module = device.create_shader_module(&vs_data);
-        let fs_module = device.create_shader_module(&fs_data);
-
-        let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            bind_group_layouts: &[&texture_bind_group_layout, &uniform_bind_group_layout],
-        });
+        // Uploads the uniforms each frame. A chunk fits one frame's worth, and
+        // another gets made whenever the gpu is still reading the last one.
+        let staging_ring = StagingRing::new(std::mem::size_of::<Uniforms>() as wgpu::BufferAddress);
 
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            layout: &render_pipeline_layout,
-            vertex_stage: wgpu::ProgrammableStageDescriptor {
-                module: &vs_module,
-                entry_point: "main",
-            },
-            fragment_stage: Some(wgpu::ProgrammableStageDescriptor {
-                module: &fs_module,
-                entry_point: "main",
-            }),
-            rasterization_state: Some(wgpu::RasterizationStateDescriptor {
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: wgpu::CullMode::Back,
-                depth_bias: 0,
-                depth_bias_slope_scale: 0.0,
-                depth_bias_clamp: 0.0,
-            }),
-            primitive_topology: wgpu::PrimitiveTopology::TriangleList,
-            color_states: &[
-                wgpu::ColorStateDescriptor {
-                    format: sc_desc.format,
-                    color_blend: wgpu::BlendDescriptor::REPLACE,
-                    alpha_blend: wgpu::BlendDescriptor::REPLACE,
-                    write_mask: wgpu::ColorWrite::ALL,
+        render_pipelines.prepare(&device, &INSTANCING, |vs, fs| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                layout: &layout.pipeline_layout,
+                vertex_stage: wgpu::ProgrammableStageDescriptor {
+                    module: &vs.module,
+                    entry_point: "main",
                 },
-            ],
-            depth_stencil_state: None,
-            index_format: wgpu::IndexFormat::Uint16,
-            vertex_buffers: &[
-                Vertex::desc(),
-            ],
-            sample_count: 1,
-            sample_mask: !0,
-            alpha_to_coverage_enabled: false,
-        });
-
-        let vertex_buffer = device
-            .create_buffer_mapped(VERTICES.len(), wgpu::BufferUsage::VERTEX)
-            .fill_from_slice(VERTICES);
-        let index_buffer = device
-            .create_buffer_mapped(INDICES.len(), wgpu::BufferUsage::INDEX)
-            .fill_from_slice(INDICES);
-        let num_indices = INDICES.len() as u32;
+                fragment_stage: Some(wgpu::ProgrammableStageDescriptor {
+                    module: &fs.module,
+                    entry_point: "main",
+                }),
+                rasterization_state: Some(wgpu::RasterizationStateDescriptor {
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: wgpu::CullMode::Back,
+                    depth_bias: 0,
+                    depth_bias_slope_scale: 0.0,
+                    depth_bias_clamp: 0.0,
+                }),
+                primitive_topology: wgpu::PrimitiveTopology::TriangleList,
+                color_states: &[
+                    wgpu::ColorStateDescriptor {
+                        format: sc_desc.format,
+                        color_blend: wgpu::BlendDescriptor::REPLACE,
+                        alpha_blend: wgpu::BlendDescriptor::REPLACE,
+                        write_mask: wgpu::ColorWrite::ALL,
+                    },
+                ],
+                depth_stencil_state: None,
+                index_format: mesh.index_format,
+                vertex_buffers: &[
+                    Vertex::desc(),
+                ],
+                sample_count: 1,
+                sample_mask: !0,
+                alpha_to_coverage_enabled: false,
+            })
+        }).unwrap();
 
         Self {
             surface,
@@ -492,10 +446,8 @@ impl State {
             queue,
             sc_desc,
             swap_chain,
-            render_pipeline,
-            vertex_buffer,
-            index_buffer,
-            num_indices,
+            render_pipelines,
+            mesh,
             diffuse_texture,
             diffuse_texture_view,
             diffuse_sampler,
@@ -504,6 +456,7 @@ impl State {
             camera_controller,
             uniform_buffer,
             uniform_bind_group,
+            staging_ring,
             uniforms,
             hidpi_factor,
             size,
@@ -528,7 +481,21 @@ impl State {
     }
 
     fn input(&mut self, event: &WindowEvent) -> bool {
-        self.camera_controller.process_events(event)
+        match event {
+            // Prints how much of the staging memory the last frame used
+            WindowEvent::KeyboardInput {
+                input: KeyboardInput {
+                    state: ElementState::Pressed,
+                    virtual_keycode: Some(VirtualKeyCode::U),
+                    ..
+                },
+                ..
+            } => {
+                println!("staging: {}", self.staging_ring.usage());
+                true
+            }
+            _ => self.camera_controller.process_events(event),
+        }
     }
 
     fn update(&mut self) {
@@ -539,13 +506,13 @@ impl State {
             todo: 0,
         });
 
-        let staging_buffer = self.device
-            .create_buffer_mapped(1, wgpu::BufferUsage::COPY_SRC)
-            .fill_from_slice(&[self.uniforms]);
-
-        encoder.copy_buffer_to_buffer(&staging_buffer, 0, &self.uniform_buffer, 0, std::mem::size_of::<Uniforms>() as wgpu::BufferAddress);
+        // Reuses the same staging memory every frame instead of making a new
+        // buffer for each upload
+        self.staging_ring.write(&self.device, &mut encoder, &self.uniform_buffer, 0, &[self.uniforms]);
+        self.staging_ring.finish();
 
         self.queue.submit(&[encoder.finish()]);
+        self.staging_ring.recall(&self.device);
     }
 
     fn render(&mut self) {
@@ -574,12 +541,12 @@ impl State {
                 depth_stencil_attachment: None,
             });
 
-            render_pass.set_pipeline(&self.render_pipeline);
+            render_pass.set_pipeline(self.render_pipelines.get(&INSTANCING).unwrap());
             render_pass.set_bind_group(0, &self.diffuse_bind_group, &[]);
             render_pass.set_bind_group(1, &self.uniform_bind_group, &[]);
-            render_pass.set_vertex_buffers(0, &[(&self.vertex_buffer, 0)]);
-            render_pass.set_index_buffer(&self.index_buffer, 0);
-            render_pass.draw_indexed(0..self.num_indices, 0, 0..self.instances.len() as u32);
+            render_pass.set_vertex_buffers(0, &[(&self.mesh.vertex_buffer, 0)]);
+            render_pass.set_index_buffer(&self.mesh.index_buffer, 0);
+            render_pass.draw_indexed(0..self.mesh.num_indices, 0, 0..self.instances.len() as u32);
         }
 
         self.queue.submit(&[
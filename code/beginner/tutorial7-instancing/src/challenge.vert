@@ -1,17 +1,13 @@
 #version 450
 
-layout(location=0) in vec3 a_position;
-layout(location=1) in vec2 a_tex_coords;
+#include "common/vertex.glsl"
 
 layout(location=0) out vec2 v_tex_coords;
 
-layout(set=1, binding=0) 
-uniform Uniforms {
-    mat4 u_view_proj;
-};
+#include "common/camera.glsl"
 
 layout(set=1, binding=1) 
-buffer Instances {
+readonly buffer Instances {
     mat4 s_models[];
 };
 
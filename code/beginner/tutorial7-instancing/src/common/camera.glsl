@@ -0,0 +1,4 @@
+layout(set=1, binding=0) 
+uniform Uniforms {
+    mat4 u_view_proj;
+};
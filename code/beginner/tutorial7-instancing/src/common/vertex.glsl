@@ -0,0 +1,2 @@
+layout(location=0) in vec3 a_position;
+layout(location=1) in vec2 a_tex_coords;
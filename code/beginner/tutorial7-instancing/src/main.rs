@@ -4,40 +4,25 @@ use winit::{
     window::{Window, WindowBuilder},
 };
 use cgmath::prelude::*;
-
-trait VBDesc {
-    fn desc<'a>() -> wgpu::VertexBufferDescriptor<'a>;
-}
+use framework::mesh::{Indices, Mesh};
+use framework::pipeline::IndexFormatPipelines;
+use framework::shader::ShaderStage;
+use framework::pod::Pod;
+use framework::staging::StagingRing;
+use framework::vertex::VertexLayout;
+
+#[cfg(feature = "hot-reload")]
+const VS_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/shader.vert");
+#[cfg(feature = "hot-reload")]
+const FS_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/shader.frag");
 
 #[repr(C)]
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, VertexLayout)]
 struct Vertex {
     position: [f32; 3],
     tex_coords: [f32; 2],
 }
 
-impl VBDesc for Vertex {
-    fn desc<'a>() -> wgpu::VertexBufferDescriptor<'a> {
-        use std::mem;
-        wgpu::VertexBufferDescriptor {
-            stride: mem::size_of::<Vertex>() as wgpu::BufferAddress,
-            step_mode: wgpu::InputStepMode::Vertex,
-            attributes: &[
-                wgpu::VertexAttributeDescriptor {
-                    offset: 0,
-                    shader_location: 0,
-                    format: wgpu::VertexFormat::Float3,
-                },
-                wgpu::VertexAttributeDescriptor {
-                    offset: mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
-                    shader_location: 1,
-                    format: wgpu::VertexFormat::Float2,
-                },
-            ]
-        }
-    }
-}
-
 const VERTICES: &[Vertex] = &[
     Vertex { position: [-0.0868241, -0.49240386, 0.0], tex_coords: [1.0 - 0.4131759, 1.0 - 0.00759614], }, // A
     Vertex { position: [-0.49513406, -0.06958647, 0.0], tex_coords: [1.0 - 0.0048659444, 1.0 - 0.43041354], }, // B
@@ -46,7 +31,9 @@ const VERTICES: &[Vertex] = &[
     Vertex { position: [0.44147372, -0.2347359, 0.0], tex_coords: [1.0 - 0.9414737, 1.0 - 0.2652641], }, // E
 ];
 
-const INDICES: &[u16] = &[
+// These get stored as u16s or u32s, whichever is smaller for the number
+// of vertices we have
+const INDICES: &[u32] = &[
     0, 1, 4,
     1, 2, 4,
     2, 3, 4,
@@ -88,6 +75,9 @@ struct Uniforms {
     view_proj: cgmath::Matrix4<f32>,
 }
 
+// Safe because a matrix of f32s is all `Uniforms` holds, so there's no padding
+unsafe impl Pod for Uniforms {}
+
 impl Uniforms {
     fn new() -> Self {
         Self {
@@ -188,43 +178,13 @@ impl CameraController {
 }
 
 #[repr(C)]
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, VertexLayout)]
+#[step_mode = "instance"]
 struct InstanceRaw {
+    #[location = 2]
     model: cgmath::Matrix4<f32>,
 }
 
-const FLOAT_SIZE: wgpu::BufferAddress = std::mem::size_of::<f32>() as wgpu::BufferAddress;
-impl VBDesc for InstanceRaw {
-    fn desc<'a>() -> wgpu::VertexBufferDescriptor<'a> {
-        wgpu::VertexBufferDescriptor {
-            stride: std::mem::size_of::<InstanceRaw>() as wgpu::BufferAddress,
-            step_mode: wgpu::InputStepMode::Instance,
-            attributes: &[
-                wgpu::VertexAttributeDescriptor {
-                    offset: 0,
-                    format: wgpu::VertexFormat::Float4,
-                    shader_location: 2,
-                },
-                wgpu::VertexAttributeDescriptor {
-                    offset: FLOAT_SIZE * 4,
-                    format: wgpu::VertexFormat::Float4,
-                    shader_location: 3,
-                },
-                wgpu::VertexAttributeDescriptor {
-                    offset: FLOAT_SIZE * 4 * 2,
-                    format: wgpu::VertexFormat::Float4,
-                    shader_location: 4,
-                },
-                wgpu::VertexAttributeDescriptor {
-                    offset: FLOAT_SIZE * 4 * 3,
-                    format: wgpu::VertexFormat::Float4,
-                    shader_location: 5,
-                },
-            ]
-        }
-    }
-}
-
 struct Instance {
     position: cgmath::Vector3<f32>,
     rotation: cgmath::Quaternion<f32>,
@@ -245,11 +205,13 @@ struct State {
     sc_desc: wgpu::SwapChainDescriptor,
     swap_chain: wgpu::SwapChain,
 
-    render_pipeline: wgpu::RenderPipeline,
+    layout: framework::layout::ReflectedLayout,
+    // The index format is part of the pipeline, so we keep one per format
+    render_pipelines: IndexFormatPipelines,
+    #[cfg(feature = "hot-reload")]
+    shader_watcher: framework::hot_reload::ShaderWatcher,
 
-    vertex_buffer: wgpu::Buffer,
-    index_buffer: wgpu::Buffer,
-    num_indices: u32,
+    mesh: Mesh,
 
     diffuse_texture: wgpu::Texture,
     diffuse_texture_view: wgpu::TextureView,
@@ -261,6 +223,7 @@ struct State {
     uniforms: Uniforms,
     uniform_buffer: wgpu::Buffer,
     uniform_bind_group: wgpu::BindGroup,
+    staging_ring: StagingRing,
 
     hidpi_factor: f64,
     size: winit::dpi::LogicalSize,
@@ -270,7 +233,7 @@ struct State {
 }
 
 impl State {
-    fn new(window: &Window) -> Self {
+    fn new(window: &Window) -> Result<Self, Box<dyn std::error::Error>> {
         let hidpi_factor = window.hidpi_factor();
         let size = window.inner_size();
         let physical_size = size.to_physical(hidpi_factor);
@@ -358,26 +321,25 @@ impl State {
             compare_function: wgpu::CompareFunction::Always,
         });
 
-        let texture_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            bindings: &[
-                wgpu::BindGroupLayoutBinding {
-                    binding: 0,
-                    visibility: wgpu::ShaderStage::FRAGMENT,
-                    ty: wgpu::BindingType::SampledTexture {
-                        multisampled: false,
-                        dimension: wgpu::TextureViewDimension::D2,
-                    },
-                },
-                wgpu::BindGroupLayoutBinding {
-                    binding: 1,
-                    visibility: wgpu::ShaderStage::FRAGMENT,
-                    ty: wgpu::BindingType::Sampler,
-                },
-            ],
-        });
+        // The shaders are compiled to spirv by build.rs. With hot-reload they also get
+        // recompiled from source at runtime when they change.
+        let vs_spirv = framework::include_spirv!("src/shader.vert");
+        let fs_spirv = framework::include_spirv!("src/shader.frag");
+        let vs_data = framework::shader::read_spirv_bytes("shader.vert", ShaderStage::Vertex, vs_spirv)?;
+        let fs_data = framework::shader::read_spirv_bytes("shader.frag", ShaderStage::Fragment, fs_spirv)?;
+
+        // Instead of writing the bind group layouts by hand, we build them from
+        // the bindings the shaders declare. Set 0 is the texture and sampler in
+        // shader.frag, and set 1 is the uniforms in shader.vert.
+        let vs_reflection = framework::reflect::reflect(&vs_data)?;
+        let fs_reflection = framework::reflect::reflect(&fs_data)?;
+        let layout = framework::layout::create_pipeline_layout(&device, &[
+            (ShaderStage::Vertex, &vs_reflection),
+            (ShaderStage::Fragment, &fs_reflection),
+        ])?;
 
         let diffuse_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &texture_bind_group_layout,
+            layout: &layout.bind_group_layouts[0],
             bindings: &[
                 wgpu::Binding {
                     binding: 0,
@@ -431,20 +393,8 @@ impl State {
             .create_buffer_mapped(instance_data.len(), wgpu::BufferUsage::VERTEX)
             .fill_from_slice(&instance_data);
 
-        let uniform_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            bindings: &[
-                wgpu::BindGroupLayoutBinding {
-                    binding: 0,
-                    visibility: wgpu::ShaderStage::VERTEX,
-                    ty: wgpu::BindingType::UniformBuffer {
-                        dynamic: false,
-                    },
-                },
-            ]
-        });
-
         let uniform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &uniform_bind_group_layout,
+            layout: &layout.bind_group_layouts[1],
             bindings: &[
                 wgpu::Binding {
                     binding: 0,
@@ -456,73 +406,31 @@ impl State {
             ],
         });
 
-        let vs_src = include_str!("shader.vert");
-        let fs_src = include_str!("shader.frag");
-        let vs_spirv = glsl_to_spirv::compile(vs_src, glsl_to_spirv::ShaderType::Vertex).unwrap();
-        let fs_spirv = glsl_to_spirv::compile(fs_src, glsl_to_spirv::ShaderType::Fragment).unwrap();
-        let vs_data = wgpu::read_spirv(vs_spirv).unwrap();
-        let fs_data = wgpu::read_spirv(fs_spirv).unwrap();
-        let vs_module = device.create_shader_module(&vs_data);
-        let fs_module = device.create_shader_module(&fs_data);
-
-        let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            bind_group_layouts: &[&texture_bind_group_layout, &uniform_bind_group_layout],
-        });
+        // Uploads the uniforms each frame. A chunk fits one frame's worth, and
+        // another gets made whenever the gpu is still reading the last one.
+        let staging_ring = StagingRing::new(std::mem::size_of::<Uniforms>() as wgpu::BufferAddress);
 
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            layout: &render_pipeline_layout,
-            vertex_stage: wgpu::ProgrammableStageDescriptor {
-                module: &vs_module,
-                entry_point: "main",
-            },
-            fragment_stage: Some(wgpu::ProgrammableStageDescriptor {
-                module: &fs_module,
-                entry_point: "main",
-            }),
-            rasterization_state: Some(wgpu::RasterizationStateDescriptor {
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: wgpu::CullMode::Back,
-                depth_bias: 0,
-                depth_bias_slope_scale: 0.0,
-                depth_bias_clamp: 0.0,
-            }),
-            primitive_topology: wgpu::PrimitiveTopology::TriangleList,
-            color_states: &[
-                wgpu::ColorStateDescriptor {
-                    format: sc_desc.format,
-                    color_blend: wgpu::BlendDescriptor::REPLACE,
-                    alpha_blend: wgpu::BlendDescriptor::REPLACE,
-                    write_mask: wgpu::ColorWrite::ALL,
-                },
-            ],
-            depth_stencil_state: None,
-            index_format: wgpu::IndexFormat::Uint16,
-            vertex_buffers: &[
-                Vertex::desc(), InstanceRaw::desc(),
-            ],
-            sample_count: 1,
-            sample_mask: !0,
-            alpha_to_coverage_enabled: false,
-        });
+        let mesh = Mesh::new(&device, VERTICES, &Indices::new(INDICES, VERTICES.len()));
 
-        let vertex_buffer = device
-            .create_buffer_mapped(VERTICES.len(), wgpu::BufferUsage::VERTEX)
-            .fill_from_slice(VERTICES);
-        let index_buffer = device
-            .create_buffer_mapped(INDICES.len(), wgpu::BufferUsage::INDEX)
-            .fill_from_slice(INDICES);
-        let num_indices = INDICES.len() as u32;
+        let mut render_pipelines = IndexFormatPipelines::new();
+        render_pipelines.prepare(mesh.index_format, |index_format| {
+            create_render_pipeline(&device, &layout.pipeline_layout, &vs_data, &fs_data, sc_desc.format, index_format)
+        })?;
 
-        Self {
+        #[cfg(feature = "hot-reload")]
+        let shader_watcher = framework::hot_reload::ShaderWatcher::new(&[VS_PATH, FS_PATH]).unwrap();
+
+        Ok(Self {
             surface,
             device,
             queue,
             sc_desc,
             swap_chain,
-            render_pipeline,
-            vertex_buffer,
-            index_buffer,
-            num_indices,
+            layout,
+            render_pipelines,
+            #[cfg(feature = "hot-reload")]
+            shader_watcher,
+            mesh,
             diffuse_texture,
             diffuse_texture_view,
             diffuse_sampler,
@@ -532,11 +440,12 @@ impl State {
             uniform_buffer,
             uniform_bind_group,
             uniforms,
+            staging_ring,
             hidpi_factor,
             size,
             instances,
             instance_buffer,
-        }
+        })
     }
 
     fn update_hidpi_and_resize(&mut self, new_hidpi_factor: f64) {
@@ -555,10 +464,83 @@ impl State {
     }
 
     fn input(&mut self, event: &WindowEvent) -> bool {
-        self.camera_controller.process_events(event)
+        match event {
+            // Prints how much of the staging memory the last frame used
+            WindowEvent::KeyboardInput {
+                input: KeyboardInput {
+                    state: ElementState::Pressed,
+                    virtual_keycode: Some(VirtualKeyCode::U),
+                    ..
+                },
+                ..
+            } => {
+                println!("staging: {}", self.staging_ring.usage());
+                true
+            }
+            _ => self.camera_controller.process_events(event),
+        }
+    }
+
+    // If the shaders on disk changed we build a new pipeline with them. When they
+    // don't compile we keep using the old one, so a typo doesn't kill the program.
+    #[cfg(feature = "hot-reload")]
+    fn reload_shaders(&mut self) {
+        if !self.shader_watcher.changed() {
+            return;
+        }
+
+        let vs_data = framework::shader::load_shader(VS_PATH.as_ref(), ShaderStage::Vertex);
+        let fs_data = framework::shader::load_shader(FS_PATH.as_ref(), ShaderStage::Fragment);
+        let render_pipelines = match (vs_data, fs_data) {
+            (Ok(vs_data), Ok(fs_data)) => self.rebuild_pipelines(&vs_data, &fs_data),
+            (Err(e), _) | (_, Err(e)) => Err(e.into()),
+        };
+        match render_pipelines {
+            Ok(render_pipelines) => {
+                self.render_pipelines = render_pipelines;
+                println!("reloaded shaders");
+            }
+            Err(e) => eprintln!("{}\nkeeping the previous pipelines", e),
+        }
+    }
+
+    // Every index format we've drawn with needs its pipeline rebuilt
+    #[cfg(feature = "hot-reload")]
+    fn rebuild_pipelines(
+        &self,
+        vs_data: &[u32],
+        fs_data: &[u32],
+    ) -> Result<IndexFormatPipelines, Box<dyn std::error::Error>> {
+        // Our bind groups were made for the bindings the shaders had when we
+        // started, so new shaders have to stick to them. Changing a binding
+        // means restarting, which builds everything from the new shaders.
+        let vs_reflection = framework::reflect::reflect(vs_data)?;
+        let fs_reflection = framework::reflect::reflect(fs_data)?;
+        let stages = [(ShaderStage::Vertex, &vs_reflection), (ShaderStage::Fragment, &fs_reflection)];
+        if !self.layout.matches(&stages)? {
+            return Err("the shaders' bindings changed, restart to use them".into());
+        }
+
+        let mut render_pipelines = IndexFormatPipelines::new();
+        for format in self.render_pipelines.formats() {
+            render_pipelines.prepare(format, |index_format| {
+                create_render_pipeline(
+                    &self.device,
+                    &self.layout.pipeline_layout,
+                    vs_data,
+                    fs_data,
+                    self.sc_desc.format,
+                    index_format,
+                )
+            })?;
+        }
+        Ok(render_pipelines)
     }
 
     fn update(&mut self) {
+        #[cfg(feature = "hot-reload")]
+        self.reload_shaders();
+
         self.camera_controller.update_camera(&mut self.camera);
         self.uniforms.update_view_proj(&self.camera);
 
@@ -566,13 +548,13 @@ impl State {
             todo: 0,
         });
 
-        let staging_buffer = self.device
-            .create_buffer_mapped(1, wgpu::BufferUsage::COPY_SRC)
-            .fill_from_slice(&[self.uniforms]);
-
-        encoder.copy_buffer_to_buffer(&staging_buffer, 0, &self.uniform_buffer, 0, std::mem::size_of::<Uniforms>() as wgpu::BufferAddress);
+        // Reuses the same staging memory every frame instead of making a new
+        // buffer for each upload
+        self.staging_ring.write(&self.device, &mut encoder, &self.uniform_buffer, 0, &[self.uniforms]);
+        self.staging_ring.finish();
 
         self.queue.submit(&[encoder.finish()]);
+        self.staging_ring.recall(&self.device);
     }
 
     fn render(&mut self) {
@@ -601,12 +583,12 @@ impl State {
                 depth_stencil_attachment: None,
             });
 
-            render_pass.set_pipeline(&self.render_pipeline);
+            render_pass.set_pipeline(self.render_pipelines.get(self.mesh.index_format).unwrap());
             render_pass.set_bind_group(0, &self.diffuse_bind_group, &[]);
             render_pass.set_bind_group(1, &self.uniform_bind_group, &[]);
-            render_pass.set_vertex_buffers(0, &[(&self.vertex_buffer, 0), (&self.instance_buffer, 0)]);
-            render_pass.set_index_buffer(&self.index_buffer, 0);
-            render_pass.draw_indexed(0..self.num_indices, 0, 0..self.instances.len() as u32);
+            render_pass.set_vertex_buffers(0, &[(&self.mesh.vertex_buffer, 0), (&self.instance_buffer, 0)]);
+            render_pass.set_index_buffer(&self.mesh.index_buffer, 0);
+            render_pass.draw_indexed(0..self.mesh.num_indices, 0, 0..self.instances.len() as u32);
         }
 
         self.queue.submit(&[
@@ -615,13 +597,71 @@ impl State {
     }
 }
 
+fn create_render_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    vs_data: &[u32],
+    fs_data: &[u32],
+    format: wgpu::TextureFormat,
+    index_format: wgpu::IndexFormat,
+) -> Result<wgpu::RenderPipeline, Box<dyn std::error::Error>> {
+    let vertex_buffers = [Vertex::desc(), InstanceRaw::desc()];
+
+    // Makes sure Vertex and InstanceRaw line up with the inputs of shader.vert
+    let reflection = framework::reflect::reflect(vs_data)?;
+    framework::reflect::check_vertex_buffers("shader.vert", &reflection, &vertex_buffers)?;
+
+    let vs_module = device.create_shader_module(vs_data);
+    let fs_module = device.create_shader_module(fs_data);
+
+    Ok(device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        layout,
+        vertex_stage: wgpu::ProgrammableStageDescriptor {
+            module: &vs_module,
+            entry_point: "main",
+        },
+        fragment_stage: Some(wgpu::ProgrammableStageDescriptor {
+            module: &fs_module,
+            entry_point: "main",
+        }),
+        rasterization_state: Some(wgpu::RasterizationStateDescriptor {
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: wgpu::CullMode::Back,
+            depth_bias: 0,
+            depth_bias_slope_scale: 0.0,
+            depth_bias_clamp: 0.0,
+        }),
+        primitive_topology: wgpu::PrimitiveTopology::TriangleList,
+        color_states: &[
+            wgpu::ColorStateDescriptor {
+                format,
+                color_blend: wgpu::BlendDescriptor::REPLACE,
+                alpha_blend: wgpu::BlendDescriptor::REPLACE,
+                write_mask: wgpu::ColorWrite::ALL,
+            },
+        ],
+        depth_stencil_state: None,
+        index_format,
+        vertex_buffers: &vertex_buffers,
+        sample_count: 1,
+        sample_mask: !0,
+        alpha_to_coverage_enabled: false,
+    }))
+}
+
 fn main() {
     let event_loop = EventLoop::new();
     let window = WindowBuilder::new()
         .build(&event_loop)
         .unwrap();
 
-    let mut state = State::new(&window);
+    let mut state = match State::new(&window) {
+        Ok(state) => state,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
     
     event_loop.run(move |event, _, control_flow| {
         match event {
@@ -661,6 +701,11 @@ fn main() {
                 state.update();
                 state.render();
                 *control_flow = ControlFlow::Wait;
+                // Wake up every so often to check the shaders, even if nothing else is happening
+                #[cfg(feature = "hot-reload")]
+                {
+                    *control_flow = ControlFlow::WaitUntil(std::time::Instant::now() + std::time::Duration::from_millis(250));
+                }
             }
             _ => *control_flow = ControlFlow::Wait,
         }
@@ -0,0 +1,346 @@
+// The derive macros for the framework crate. Use them through
+// `framework::vertex::VertexLayout` instead of depending on this directly.
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote, ToTokens};
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, Attribute, Data, DeriveInput, Error, GenericArgument, Lit, Meta, NestedMeta, PathArguments, Type};
+
+// Implements `VertexLayout` for a `#[repr(C)]` struct, with one vertex
+// attribute per field. Locations count up from 0 in field order, and a matrix
+// takes up one location per column. The attributes it understands are:
+//
+// - `#[step_mode = "instance"]` on the struct, for per instance data
+// - `#[location = N]` on a field, to start it (and the fields after it) at N
+// - `#[format = "Uchar4Norm"]` on a field, when the format can't be worked
+//   out from the field's type
+#[proc_macro_derive(VertexLayout, attributes(step_mode, location, format))]
+pub fn derive_vertex_layout(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match vertex_layout(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn vertex_layout(input: &DeriveInput) -> Result<TokenStream2, Error> {
+    let name = &input.ident;
+
+    // Without repr(C) rust is free to reorder the fields, and our offsets
+    // would be wrong
+    if !is_repr_c(&input.attrs) {
+        return Err(Error::new(
+            name.span(),
+            "VertexLayout needs #[repr(C)] so the fields stay in the order they're written",
+        ));
+    }
+    if !input.generics.params.is_empty() {
+        return Err(Error::new(input.generics.span(), "VertexLayout doesn't support generic structs"));
+    }
+
+    let fields = match &input.data {
+        Data::Struct(data) if !data.fields.is_empty() => &data.fields,
+        _ => return Err(Error::new(name.span(), "VertexLayout only works on structs with fields")),
+    };
+
+    let mut step_mode = quote!(::wgpu::InputStepMode::Vertex);
+    for attr in &input.attrs {
+        if attr.path.is_ident("step_mode") {
+            step_mode = match string_value(attr)?.as_str() {
+                "vertex" => quote!(::wgpu::InputStepMode::Vertex),
+                "instance" => quote!(::wgpu::InputStepMode::Instance),
+                _ => return Err(Error::new(attr.span(), "step_mode has to be \"vertex\" or \"instance\"")),
+            };
+        }
+    }
+
+    let mut offsets = Vec::new();
+    let mut attributes = Vec::new();
+    let mut location = 0;
+    let mut previous: Option<(syn::Ident, &Type)> = None;
+    for (i, field) in fields.iter().enumerate() {
+        let ty = &field.ty;
+        let mut format = None;
+        for attr in &field.attrs {
+            if attr.path.is_ident("location") {
+                location = int_value(attr)?;
+            } else if attr.path.is_ident("format") {
+                let value = string_value(attr)?;
+                format = Some(syn::parse_str::<syn::Ident>(&value).map_err(|_| {
+                    Error::new(attr.span(), format!("{:?} isn't a wgpu::VertexFormat", value))
+                })?);
+            }
+        }
+
+        // With #[format] the field can be any type, as long as it's one attribute
+        let (format, columns, column_size) = match (format, field_layout(ty)) {
+            (Some(format), Ok(layout)) => (format, layout.columns, layout.column_size()),
+            (Some(format), Err(_)) => (format, 1, 0),
+            (None, layout) => {
+                let layout = layout?;
+                let format = layout.format().ok_or_else(|| {
+                    Error::new(
+                        ty.span(),
+                        format!(
+                            "`{}` doesn't match any wgpu::VertexFormat, pick one with #[format = \"...\"]",
+                            ty.to_token_stream()
+                        ),
+                    )
+                })?;
+                (format, layout.columns, layout.column_size())
+            }
+        };
+
+        // Each field starts after the one before it, rounded up to its alignment
+        let offset = format_ident!("OFFSET_{}", i);
+        offsets.push(match &previous {
+            None => quote! {
+                const #offset: ::wgpu::BufferAddress = 0;
+            },
+            // `div_ceil` is too new for the toolchain the tutorial targets, so
+            // this pads out whatever's left over instead
+            Some((previous_offset, previous_ty)) => quote! {
+                const #offset: ::wgpu::BufferAddress = {
+                    let end = #previous_offset + ::std::mem::size_of::<#previous_ty>() as ::wgpu::BufferAddress;
+                    let align = ::std::mem::align_of::<#ty>() as ::wgpu::BufferAddress;
+                    end + (align - end % align) % align
+                };
+            },
+        });
+
+        for column in 0..columns {
+            let column_offset = u64::from(column * column_size);
+            attributes.push(quote! {
+                ::wgpu::VertexAttributeDescriptor {
+                    offset: #offset + #column_offset,
+                    format: ::wgpu::VertexFormat::#format,
+                    shader_location: #location,
+                }
+            });
+            location += 1;
+        }
+        previous = Some((offset, ty));
+    }
+
+    Ok(quote! {
+        impl ::framework::vertex::VertexLayout for #name {
+            fn desc<'a>() -> ::wgpu::VertexBufferDescriptor<'a> {
+                #(#offsets)*
+                const ATTRIBUTES: &[::wgpu::VertexAttributeDescriptor] = &[#(#attributes),*];
+                ::wgpu::VertexBufferDescriptor {
+                    stride: ::std::mem::size_of::<#name>() as ::wgpu::BufferAddress,
+                    step_mode: #step_mode,
+                    attributes: ATTRIBUTES,
+                }
+            }
+        }
+    })
+}
+
+fn is_repr_c(attrs: &[Attribute]) -> bool {
+    attrs.iter().filter(|attr| attr.path.is_ident("repr")).any(|attr| match attr.parse_meta() {
+        Ok(Meta::List(list)) => list.nested.iter().any(|nested| match nested {
+            NestedMeta::Meta(meta) => meta.path().is_ident("C"),
+            _ => false,
+        }),
+        _ => false,
+    })
+}
+
+fn value(attr: &Attribute) -> Result<Lit, Error> {
+    match attr.parse_meta()? {
+        Meta::NameValue(meta) => Ok(meta.lit),
+        meta => Err(Error::new(meta.span(), "expected #[name = value]")),
+    }
+}
+
+fn string_value(attr: &Attribute) -> Result<String, Error> {
+    match value(attr)? {
+        Lit::Str(s) => Ok(s.value()),
+        lit => Err(Error::new(lit.span(), "expected a string")),
+    }
+}
+
+fn int_value(attr: &Attribute) -> Result<u32, Error> {
+    match value(attr)? {
+        Lit::Int(i) => i.base10_parse(),
+        lit => Err(Error::new(lit.span(), "expected a number")),
+    }
+}
+
+#[derive(Copy, Clone)]
+enum Scalar {
+    F32,
+    U32,
+    I32,
+    U16,
+    I16,
+    U8,
+    I8,
+}
+
+impl Scalar {
+    fn from_type(ty: &Type) -> Option<Self> {
+        let ident = match ty {
+            Type::Path(path) if path.qself.is_none() => path.path.get_ident()?,
+            _ => return None,
+        };
+        Some(match ident.to_string().as_str() {
+            "f32" => Scalar::F32,
+            "u32" => Scalar::U32,
+            "i32" => Scalar::I32,
+            "u16" => Scalar::U16,
+            "i16" => Scalar::I16,
+            "u8" => Scalar::U8,
+            "i8" => Scalar::I8,
+            _ => return None,
+        })
+    }
+
+    fn size(self) -> u32 {
+        match self {
+            Scalar::F32 | Scalar::U32 | Scalar::I32 => 4,
+            Scalar::U16 | Scalar::I16 => 2,
+            Scalar::U8 | Scalar::I8 => 1,
+        }
+    }
+}
+
+// How a field's type is split up into vertex attributes
+struct FieldLayout {
+    scalar: Scalar,
+    components: u32,
+    // More than one for matrices, which get an attribute per column
+    columns: u32,
+}
+
+impl FieldLayout {
+    fn column_size(&self) -> u32 {
+        self.scalar.size() * self.components
+    }
+
+    // The smaller integer types only come in 2 and 4 components, and there's
+    // nothing to say whether they're normalized, so those need #[format]
+    fn format(&self) -> Option<syn::Ident> {
+        let name = match (self.scalar, self.components) {
+            (Scalar::F32, 1) => "Float".to_string(),
+            (Scalar::U32, 1) => "Uint".to_string(),
+            (Scalar::I32, 1) => "Int".to_string(),
+            (Scalar::F32, n @ 2..=4) => format!("Float{}", n),
+            (Scalar::U32, n @ 2..=4) => format!("Uint{}", n),
+            (Scalar::I32, n @ 2..=4) => format!("Int{}", n),
+            (Scalar::U16, n @ 2) | (Scalar::U16, n @ 4) => format!("Ushort{}", n),
+            (Scalar::I16, n @ 2) | (Scalar::I16, n @ 4) => format!("Short{}", n),
+            (Scalar::U8, n @ 2) | (Scalar::U8, n @ 4) => format!("Uchar{}", n),
+            (Scalar::I8, n @ 2) | (Scalar::I8, n @ 4) => format!("Char{}", n),
+            _ => return None,
+        };
+        Some(format_ident!("{}", name))
+    }
+}
+
+fn array_length(len: &syn::Expr) -> Result<u32, Error> {
+    match len {
+        syn::Expr::Lit(syn::ExprLit { lit: Lit::Int(i), .. }) => i.base10_parse(),
+        _ => Err(Error::new(len.span(), "array lengths have to be a number")),
+    }
+}
+
+fn field_layout(ty: &Type) -> Result<FieldLayout, Error> {
+    let unsupported = || {
+        Error::new(
+            ty.span(),
+            format!("`{}` can't be used in a vertex buffer", ty.to_token_stream()),
+        )
+    };
+
+    if let Some(scalar) = Scalar::from_type(ty) {
+        return Ok(FieldLayout { scalar, components: 1, columns: 1 });
+    }
+
+    match ty {
+        // [f32; 3]
+        Type::Array(array) => {
+            if let Some(scalar) = Scalar::from_type(&array.elem) {
+                let components = array_length(&array.len)?;
+                return Ok(FieldLayout { scalar, components, columns: 1 });
+            }
+            // [[f32; 4]; 4] is a matrix
+            if let Type::Array(column) = &*array.elem {
+                let scalar = Scalar::from_type(&column.elem).ok_or_else(unsupported)?;
+                let components = array_length(&column.len)?;
+                let columns = array_length(&array.len)?;
+                return Ok(FieldLayout { scalar, components, columns });
+            }
+            Err(unsupported())
+        }
+        // cgmath's Vector3<f32>, Point3<f32>, Matrix4<f32> and so on
+        Type::Path(path) => {
+            let segment = path.path.segments.last().ok_or_else(unsupported)?;
+            let scalar = match &segment.arguments {
+                PathArguments::AngleBracketed(args) => match args.args.first() {
+                    Some(GenericArgument::Type(ty)) => Scalar::from_type(ty),
+                    _ => None,
+                },
+                _ => None,
+            }
+            .ok_or_else(unsupported)?;
+
+            let (components, columns) = match segment.ident.to_string().as_str() {
+                "Vector2" | "Point2" => (2, 1),
+                "Vector3" | "Point3" => (3, 1),
+                "Vector4" => (4, 1),
+                "Matrix2" => (2, 2),
+                "Matrix3" => (3, 3),
+                "Matrix4" => (4, 4),
+                _ => return Err(unsupported()),
+            };
+            Ok(FieldLayout { scalar, components, columns })
+        }
+        _ => Err(unsupported()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(src: &str) -> Result<TokenStream2, String> {
+        let input = syn::parse_str::<DeriveInput>(src).unwrap();
+        vertex_layout(&input).map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn structs_need_repr_c() {
+        let error = expand("struct Vertex { position: [f32; 3] }").unwrap_err();
+        assert!(error.contains("#[repr(C)]"), "{}", error);
+    }
+
+    #[test]
+    fn types_without_a_format_are_errors() {
+        let error = expand("#[repr(C)] struct Vertex { color: [u8; 3] }").unwrap_err();
+        assert!(error.contains("`[u8 ; 3]` doesn't match any wgpu::VertexFormat"), "{}", error);
+
+        let error = expand("#[repr(C)] struct Vertex { name: String }").unwrap_err();
+        assert!(error.contains("can't be used in a vertex buffer"), "{}", error);
+
+        let error = expand("#[repr(C)] #[step_mode = \"index\"] struct Vertex { position: [f32; 3] }").unwrap_err();
+        assert!(error.contains("step_mode"), "{}", error);
+    }
+
+    #[test]
+    fn matrices_get_an_attribute_per_column() {
+        let tokens = expand("#[repr(C)] struct InstanceRaw { #[location = 2] model: [[f32; 4]; 4] }")
+            .unwrap()
+            .to_string();
+        for (location, offset) in [(2u32, 0u64), (3, 16), (4, 32), (5, 48)].iter() {
+            let attribute = format!(
+                "offset : OFFSET_0 + {}u64 , format : :: wgpu :: VertexFormat :: Float4 , shader_location : {}u32",
+                offset, location
+            );
+            assert!(tokens.contains(&attribute), "no {} in {}", attribute, tokens);
+        }
+    }
+}
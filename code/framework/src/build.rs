@@ -0,0 +1,95 @@
+// Helpers for build scripts
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::preprocessor::{self, Defines};
+use crate::shader::{self, ShaderStage};
+
+// Compiles every shader under `dirs` into spirv in OUT_DIR, keeping the
+// relative path, so "src/shader.vert" becomes "$OUT_DIR/src/shader.vert.spv"
+// and "src/shader.wgsl" becomes "$OUT_DIR/src/shader.wgsl.spv".
+// Load them with `include_spirv!`. If any of them don't compile we print the
+// errors and panic, which fails the build.
+pub fn compile_shaders(dirs: &[&str]) {
+    let mut shaders = Vec::new();
+    for dir in dirs {
+        println!("cargo:rerun-if-changed={}", dir);
+        find_shaders(Path::new(dir), &mut shaders);
+    }
+    compile_all(shaders.into_iter().map(|path| (path, Vec::new())));
+}
+
+// Compiles each shader once with the defines it's paired with, for shaders
+// that `#ifdef` between a few versions of themselves. The defines go on the
+// end of the name, so ("src/shader.vert", &["USE_VERTEX_COLOR"]) becomes
+// "$OUT_DIR/src/shader.vert.USE_VERTEX_COLOR.spv", which is
+// `include_spirv!("src/shader.vert", "USE_VERTEX_COLOR")`. A shader with no
+// defines ends up with the same name `compile_shaders` would give it.
+pub fn compile_shader_variants(variants: &[(&str, &[&str])]) {
+    compile_all(variants.iter().map(|&(path, defines)| (PathBuf::from(path), defines.to_vec())));
+}
+
+fn compile_all<'a>(shaders: impl Iterator<Item = (PathBuf, Vec<&'a str>)>) {
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+
+    let mut failed = 0;
+    for (path, defines) in shaders {
+        // Editing a file the shader includes has to rebuild it too. If the
+        // shader doesn't preprocess, compiling it below reports why.
+        let define_set = defines.iter().cloned().collect::<Defines>();
+        println!("cargo:rerun-if-changed={}", path.display());
+        if let Ok(preprocessed) = preprocessor::preprocess_with_defines(&path, &define_set) {
+            for file in &preprocessed.files[1..] {
+                println!("cargo:rerun-if-changed={}", file.display());
+            }
+        }
+
+        // A wgsl file keeps all of its entry points, whatever stage they're for
+        let spirv = match ShaderStage::from_path(&path) {
+            Some(stage) => shader::load_shader_variant(&path, stage, &define_set),
+            None => shader::load_wgsl_variant(&path, None, &define_set),
+        };
+        let spirv = match spirv {
+            Ok(spirv) => spirv,
+            Err(e) => {
+                eprintln!("{}\n", e);
+                failed += 1;
+                continue;
+            }
+        };
+
+        let mut spv_name = path.file_name().unwrap().to_os_string();
+        for define in &defines {
+            spv_name.push(".");
+            spv_name.push(define);
+        }
+        spv_name.push(".spv");
+        let out_path = out_dir.join(&path).with_file_name(spv_name);
+        fs::create_dir_all(out_path.parent().unwrap()).unwrap();
+
+        let bytes = spirv.iter().flat_map(|word| word.to_le_bytes().to_vec()).collect::<Vec<_>>();
+        fs::write(&out_path, bytes).unwrap();
+    }
+
+    if failed > 0 {
+        panic!("{} shader(s) failed to compile", failed);
+    }
+}
+
+fn find_shaders(dir: &Path, shaders: &mut Vec<PathBuf>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        // Not every crate has an examples folder
+        Err(_) => return,
+    };
+
+    for entry in entries {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            find_shaders(&path, shaders);
+        } else if ShaderStage::from_path(&path).is_some() || path.extension() == Some("wgsl".as_ref()) {
+            shaders.push(path);
+        }
+    }
+}
@@ -0,0 +1,62 @@
+// The camera and uniforms from tutorial6 on, for code outside the tutorials
+// that wants to draw with their shaders
+use cgmath::prelude::*;
+
+use crate::pod::Pod;
+
+// cgmath is built for OpenGL, where z goes from -1 to 1 in clip space. wgpu
+// wants 0 to 1, and has y pointing the other way in texture coordinates.
+#[rustfmt::skip]
+pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
+    1.0, 0.0, 0.0, 0.0,
+    0.0, -1.0, 0.0, 0.0,
+    0.0, 0.0, 0.5, 0.0,
+    0.0, 0.0, 0.5, 1.0,
+);
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Camera {
+    pub eye: cgmath::Point3<f32>,
+    pub target: cgmath::Point3<f32>,
+    pub up: cgmath::Vector3<f32>,
+    pub aspect: f32,
+    pub fovy: f32,
+    pub znear: f32,
+    pub zfar: f32,
+}
+
+impl Camera {
+    pub fn build_view_projection_matrix(&self) -> cgmath::Matrix4<f32> {
+        let view = cgmath::Matrix4::look_at(self.eye, self.target, self.up);
+        let proj = cgmath::perspective(cgmath::Deg(self.fovy), self.aspect, self.znear, self.zfar);
+        proj * view
+    }
+}
+
+// What the vertex shader sees at set 1, binding 0
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Uniforms {
+    pub view_proj: cgmath::Matrix4<f32>,
+}
+
+// Safe because a matrix of f32s is all `Uniforms` holds, so there's no padding
+unsafe impl Pod for Uniforms {}
+
+impl Uniforms {
+    pub fn new() -> Self {
+        Self {
+            view_proj: cgmath::Matrix4::identity(),
+        }
+    }
+
+    pub fn update_view_proj(&mut self, camera: &Camera) {
+        self.view_proj = OPENGL_TO_WGPU_MATRIX * camera.build_view_projection_matrix();
+    }
+}
+
+impl Default for Uniforms {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,378 @@
+// Loads glTF 2.0 scenes, both `.gltf` files (with their buffers and images
+// next to them) and `.glb` files (with everything packed inside). Meshes come
+// out in the same shape as tutorial7's: a `Vertex` buffer, an `InstanceRaw`
+// buffer made from the nodes that use the mesh, and a bind group per material
+// with the base color texture and its sampler.
+//
+// glTF uses the same conventions we do: y is up, front faces are counter
+// clockwise and texture coordinates start at the top left, so nothing needs
+// flipping.
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use cgmath::prelude::*;
+
+use crate::instance::{self, InstanceRaw};
+use crate::mesh::{MeshData, Vertex};
+use crate::texture::{self, Texture};
+
+#[derive(Debug)]
+pub enum GltfError {
+    Import { path: PathBuf, error: gltf::Error },
+    // We only know how to draw triangle lists
+    UnsupportedMode { mesh: String, mode: gltf::mesh::Mode },
+    MissingPositions { mesh: String },
+}
+
+impl fmt::Display for GltfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GltfError::Import { path, error } => write!(f, "couldn't load {}: {}", path.display(), error),
+            GltfError::UnsupportedMode { mesh, mode } => {
+                write!(f, "mesh {} uses {:?}, but only triangles are supported", mesh, mode)
+            }
+            GltfError::MissingPositions { mesh } => write!(f, "mesh {} has a primitive without positions", mesh),
+        }
+    }
+}
+
+impl std::error::Error for GltfError {}
+
+pub struct GltfPrimitive {
+    pub data: MeshData,
+    // One per vertex, if the file has them
+    pub normals: Option<Vec<[f32; 3]>>,
+    // Primitives without a material get the default one, which is the last
+    // in `GltfData::materials`
+    pub material: usize,
+}
+
+pub struct GltfMesh {
+    pub name: String,
+    pub primitives: Vec<GltfPrimitive>,
+    // One for each node in the scene that uses this mesh, placed, turned and
+    // scaled by the node and everything above it
+    pub instances: Vec<InstanceRaw>,
+}
+
+pub struct GltfMaterial {
+    pub name: String,
+    // The base color texture as 4 bytes per pixel, or a single pixel of the
+    // base color if there's no texture
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+    pub sampler: wgpu::SamplerDescriptor,
+}
+
+// A glTF scene loaded into memory, but not onto the gpu yet
+pub struct GltfData {
+    pub meshes: Vec<GltfMesh>,
+    pub materials: Vec<GltfMaterial>,
+}
+
+pub fn load_gltf<P: AsRef<Path>>(path: P) -> Result<GltfData, GltfError> {
+    let path = path.as_ref();
+    // `import` works out whether it's a .gltf or a .glb, and loads any
+    // buffers and images the file points at
+    let (document, buffers, images) = gltf::import(path).map_err(|error| GltfError::Import {
+        path: path.to_owned(),
+        error,
+    })?;
+
+    let mut materials = document.materials().map(|material| load_material(&material, &images)).collect::<Vec<_>>();
+    let default_material = materials.len();
+    // glTF's default material is plain white
+    materials.push(solid_color("default".to_owned(), [1.0, 1.0, 1.0, 1.0]));
+
+    let mut meshes = Vec::new();
+    for mesh in document.meshes() {
+        let name = mesh.name().map(str::to_owned).unwrap_or_else(|| format!("#{}", mesh.index()));
+        let mut primitives = Vec::new();
+        for primitive in mesh.primitives() {
+            if primitive.mode() != gltf::mesh::Mode::Triangles {
+                return Err(GltfError::UnsupportedMode { mesh: name, mode: primitive.mode() });
+            }
+
+            let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+            let positions = match reader.read_positions() {
+                Some(positions) => positions,
+                None => return Err(GltfError::MissingPositions { mesh: name }),
+            };
+            let mut tex_coords = reader.read_tex_coords(0).map(|tex_coords| tex_coords.into_f32());
+            let vertices = positions
+                .map(|position| Vertex {
+                    position,
+                    tex_coords: tex_coords.as_mut().and_then(Iterator::next).unwrap_or([0.0, 0.0]),
+                })
+                .collect::<Vec<_>>();
+            // Without indices every three vertices are a triangle
+            let indices = match reader.read_indices() {
+                Some(indices) => indices.into_u32().collect(),
+                None => (0..vertices.len() as u32).collect(),
+            };
+
+            primitives.push(GltfPrimitive {
+                data: MeshData { vertices, indices },
+                normals: reader.read_normals().map(Iterator::collect),
+                material: primitive.material().index().unwrap_or(default_material),
+            });
+        }
+        meshes.push(GltfMesh {
+            name,
+            primitives,
+            instances: Vec::new(),
+        });
+    }
+
+    // Files don't have to say which scene to show, in which case the first
+    // one is as good as any
+    if let Some(scene) = document.default_scene().or_else(|| document.scenes().next()) {
+        let root = cgmath::Matrix4::identity();
+        for node in scene.nodes() {
+            add_instances(&node, &root, &mut meshes);
+        }
+    }
+
+    Ok(GltfData { meshes, materials })
+}
+
+// Walks down the node tree, working out where each node ends up in the world.
+// The whole matrix goes in the instance, so scale survives, even when a
+// scaled parent turns its children into something a position, rotation and
+// scale can't describe.
+fn add_instances(node: &gltf::Node, parent: &cgmath::Matrix4<f32>, meshes: &mut [GltfMesh]) {
+    let model = parent * cgmath::Matrix4::from(node.transform().matrix());
+    if let Some(mesh) = node.mesh() {
+        meshes[mesh.index()].instances.push(InstanceRaw { model });
+    }
+    for child in node.children() {
+        add_instances(&child, &model, meshes);
+    }
+}
+
+fn load_material(material: &gltf::Material, images: &[gltf::image::Data]) -> GltfMaterial {
+    let name = material
+        .name()
+        .map(str::to_owned)
+        .unwrap_or_else(|| format!("#{}", material.index().unwrap()));
+
+    let pbr = material.pbr_metallic_roughness();
+    match pbr.base_color_texture() {
+        Some(info) => {
+            let texture = info.texture();
+            let image = &images[texture.source().index()];
+            GltfMaterial {
+                name,
+                width: image.width,
+                height: image.height,
+                pixels: to_rgba(image),
+                sampler: sampler_descriptor(&texture.sampler()),
+            }
+        }
+        None => solid_color(name, pbr.base_color_factor()),
+    }
+}
+
+fn solid_color(name: String, [r, g, b, a]: [f32; 4]) -> GltfMaterial {
+    // The base color is linear, and our textures are sRGB
+    GltfMaterial {
+        name,
+        width: 1,
+        height: 1,
+        pixels: vec![linear_to_srgb(r), linear_to_srgb(g), linear_to_srgb(b), (a * 255.0).round() as u8],
+        sampler: texture::sampler_descriptor(wgpu::AddressMode::Repeat),
+    }
+}
+
+fn linear_to_srgb(value: f32) -> u8 {
+    let value = value.clamp(0.0, 1.0);
+    let srgb = if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    };
+    (srgb * 255.0).round() as u8
+}
+
+// Images come in whatever format they were saved in, and our textures are all
+// `Rgba8UnormSrgb`
+fn to_rgba(image: &gltf::image::Data) -> Vec<u8> {
+    use gltf::image::Format;
+
+    // 16 bit channels keep their top 8 bits
+    let pixels = match image.format {
+        Format::R16 | Format::R16G16 | Format::R16G16B16 | Format::R16G16B16A16 => image
+            .pixels
+            .chunks(2)
+            .map(|channel| (u16::from_ne_bytes([channel[0], channel[1]]) >> 8) as u8)
+            .collect(),
+        _ => image.pixels.clone(),
+    };
+
+    // Images with one or two channels are grayscale, with alpha for two
+    let channels = match image.format {
+        Format::R8 | Format::R16 => 1,
+        Format::R8G8 | Format::R16G16 => 2,
+        Format::R8G8B8 | Format::B8G8R8 | Format::R16G16B16 => 3,
+        Format::R8G8B8A8 | Format::B8G8R8A8 | Format::R16G16B16A16 => 4,
+    };
+    let bgr = matches!(image.format, Format::B8G8R8 | Format::B8G8R8A8);
+
+    pixels
+        .chunks(channels)
+        .flat_map(|pixel| match *pixel {
+            [l] => [l, l, l, 255],
+            [l, a] => [l, l, l, a],
+            [b, g, r] if bgr => [r, g, b, 255],
+            [r, g, b] => [r, g, b, 255],
+            [b, g, r, a] if bgr => [r, g, b, a],
+            [r, g, b, a] => [r, g, b, a],
+            _ => unreachable!(),
+        }.to_vec())
+        .collect()
+}
+
+fn sampler_descriptor(sampler: &gltf::texture::Sampler) -> wgpu::SamplerDescriptor {
+    use gltf::texture::{MagFilter, MinFilter, WrappingMode};
+
+    let address_mode = |mode| match mode {
+        WrappingMode::ClampToEdge => wgpu::AddressMode::ClampToEdge,
+        WrappingMode::MirroredRepeat => wgpu::AddressMode::MirrorRepeat,
+        WrappingMode::Repeat => wgpu::AddressMode::Repeat,
+    };
+    let mag_filter = match sampler.mag_filter() {
+        Some(MagFilter::Nearest) => wgpu::FilterMode::Nearest,
+        Some(MagFilter::Linear) | None => wgpu::FilterMode::Linear,
+    };
+    let (min_filter, mipmap_filter) = match sampler.min_filter() {
+        Some(MinFilter::Nearest) | Some(MinFilter::NearestMipmapNearest) => {
+            (wgpu::FilterMode::Nearest, wgpu::FilterMode::Nearest)
+        }
+        Some(MinFilter::NearestMipmapLinear) => (wgpu::FilterMode::Nearest, wgpu::FilterMode::Linear),
+        Some(MinFilter::LinearMipmapNearest) => (wgpu::FilterMode::Linear, wgpu::FilterMode::Nearest),
+        Some(MinFilter::Linear) | Some(MinFilter::LinearMipmapLinear) | None => {
+            (wgpu::FilterMode::Linear, wgpu::FilterMode::Linear)
+        }
+    };
+
+    wgpu::SamplerDescriptor {
+        address_mode_u: address_mode(sampler.wrap_s()),
+        address_mode_v: address_mode(sampler.wrap_t()),
+        mag_filter,
+        min_filter,
+        mipmap_filter,
+        ..texture::sampler_descriptor(wgpu::AddressMode::Repeat)
+    }
+}
+
+pub struct Primitive {
+    pub vertex_buffer: wgpu::Buffer,
+    // Bind this as its own vertex buffer, with a `Float3` per vertex
+    pub normal_buffer: Option<wgpu::Buffer>,
+    pub index_buffer: wgpu::Buffer,
+    // The pipeline drawing this primitive needs to use the same format
+    pub index_format: wgpu::IndexFormat,
+    pub num_indices: u32,
+    pub material: usize,
+}
+
+pub struct Mesh {
+    pub name: String,
+    pub primitives: Vec<Primitive>,
+    // `InstanceRaw`s, to go in the vertex buffer slot after the primitive's
+    pub instance_buffer: wgpu::Buffer,
+    pub num_instances: u32,
+}
+
+pub struct Material {
+    pub name: String,
+    pub diffuse_texture: Texture,
+    // Made with the layout passed to `Scene::load`
+    pub bind_group: wgpu::BindGroup,
+}
+
+// A glTF scene with its buffers, textures and bind groups on the gpu
+pub struct Scene {
+    pub meshes: Vec<Mesh>,
+    pub materials: Vec<Material>,
+}
+
+impl Scene {
+    // `texture_bind_group_layout` should look like
+    // `Texture::create_bind_group_layout`, which is what the tutorials use
+    pub fn load<P: AsRef<Path>>(
+        device: &wgpu::Device,
+        queue: &mut wgpu::Queue,
+        path: P,
+        texture_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Result<Self, GltfError> {
+        let data = load_gltf(path)?;
+        Ok(Self::from_data(device, queue, &data, texture_bind_group_layout))
+    }
+
+    pub fn from_data(
+        device: &wgpu::Device,
+        queue: &mut wgpu::Queue,
+        data: &GltfData,
+        texture_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let materials = data
+            .materials
+            .iter()
+            .map(|material| {
+                let diffuse_texture = Texture::from_rgba(
+                    device,
+                    queue,
+                    material.width,
+                    material.height,
+                    &material.pixels,
+                    &material.sampler,
+                );
+                let bind_group = diffuse_texture.create_bind_group(device, texture_bind_group_layout);
+                Material {
+                    name: material.name.clone(),
+                    diffuse_texture,
+                    bind_group,
+                }
+            })
+            .collect();
+
+        // Meshes that no node uses have nothing to draw, and wgpu can't make
+        // an empty instance buffer for them
+        let meshes = data
+            .meshes
+            .iter()
+            .filter(|mesh| !mesh.instances.is_empty())
+            .map(|mesh| {
+                let primitives = mesh
+                    .primitives
+                    .iter()
+                    .map(|primitive| {
+                        let (index_buffer, index_format) = primitive.data.create_packed_index_buffer(device);
+                        Primitive {
+                            vertex_buffer: primitive.data.create_vertex_buffer(device),
+                            normal_buffer: primitive.normals.as_ref().map(|normals| {
+                                device
+                                    .create_buffer_mapped(normals.len(), wgpu::BufferUsage::VERTEX)
+                                    .fill_from_slice(normals)
+                            }),
+                            index_buffer,
+                            index_format,
+                            num_indices: primitive.data.num_indices(),
+                            material: primitive.material,
+                        }
+                    })
+                    .collect();
+                Mesh {
+                    name: mesh.name.clone(),
+                    primitives,
+                    instance_buffer: instance::create_instance_buffer(device, &mesh.instances),
+                    num_instances: mesh.instances.len() as u32,
+                }
+            })
+            .collect();
+
+        Self { meshes, materials }
+    }
+}
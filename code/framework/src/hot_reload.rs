@@ -0,0 +1,91 @@
+use notify::{DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::time::Duration;
+
+use crate::preprocessor;
+
+// Watches shader files on disk so the pipelines using them can be rebuilt
+// while the program is running. Files the shaders `#include` are watched too.
+pub struct ShaderWatcher {
+    watcher: RecommendedWatcher,
+    receiver: Receiver<DebouncedEvent>,
+    shaders: Vec<PathBuf>,
+    // The shaders and everything they include
+    files: HashSet<PathBuf>,
+    directories: HashSet<PathBuf>,
+}
+
+impl ShaderWatcher {
+    pub fn new<P: AsRef<Path>>(paths: &[P]) -> notify::Result<Self> {
+        let (sender, receiver) = mpsc::channel();
+        // Editors often save multiple times in a row, so we wait for things to settle down
+        let watcher = notify::watcher(sender, Duration::from_millis(200))?;
+
+        let mut shader_watcher = Self {
+            watcher,
+            receiver,
+            shaders: paths.iter().map(|path| path.as_ref().to_path_buf()).collect(),
+            files: HashSet::new(),
+            directories: HashSet::new(),
+        };
+        shader_watcher.update_files()?;
+        Ok(shader_watcher)
+    }
+
+    // Returns true if any of the watched shaders changed since the last call.
+    // This never blocks, so it's fine to call it every frame.
+    pub fn changed(&mut self) -> bool {
+        let mut changed = false;
+        for event in self.receiver.try_iter() {
+            match event {
+                DebouncedEvent::Create(path)
+                | DebouncedEvent::Write(path)
+                | DebouncedEvent::Rename(_, path) => {
+                    changed |= path
+                        .canonicalize()
+                        .map(|path| self.files.contains(&path))
+                        .unwrap_or(false);
+                }
+                DebouncedEvent::Error(e, _) => eprintln!("shader watcher error: {}", e),
+                _ => {}
+            }
+        }
+
+        // The change might have added or removed an include
+        if changed {
+            if let Err(e) = self.update_files() {
+                eprintln!("shader watcher error: {}", e);
+            }
+        }
+        changed
+    }
+
+    fn update_files(&mut self) -> notify::Result<()> {
+        self.files.clear();
+        for shader in &self.shaders {
+            self.files.insert(shader.canonicalize()?);
+            // If the shader doesn't preprocess right now we still watch the
+            // shader itself, and pick up its includes once it's fixed
+            if let Ok(preprocessed) = preprocessor::preprocess(shader) {
+                for file in preprocessed.files {
+                    if let Ok(file) = file.canonicalize() {
+                        self.files.insert(file);
+                    }
+                }
+            }
+        }
+
+        // A lot of editors save by writing a new file and renaming it over the
+        // old one, so we watch the directories rather than the files themselves
+        for file in &self.files {
+            if let Some(directory) = file.parent() {
+                if self.directories.insert(directory.to_path_buf()) {
+                    self.watcher.watch(directory, RecursiveMode::NonRecursive)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,38 @@
+// The instances from tutorial7, for code outside the tutorial that wants to
+// feed its pipeline
+use crate::pod::Pod;
+use crate::vertex::VertexLayout;
+
+// What the vertex shader sees, in the vertex buffer after the mesh's
+#[repr(C)]
+#[derive(Debug, Copy, Clone, VertexLayout)]
+#[step_mode = "instance"]
+pub struct InstanceRaw {
+    #[location = 2]
+    pub model: cgmath::Matrix4<f32>,
+}
+
+// Safe because it's only a matrix of f32s, so there's no padding
+unsafe impl Pod for InstanceRaw {}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Instance {
+    pub position: cgmath::Vector3<f32>,
+    pub rotation: cgmath::Quaternion<f32>,
+}
+
+impl Instance {
+    pub fn to_raw(&self) -> InstanceRaw {
+        let model = cgmath::Matrix4::from_translation(self.position)
+            * cgmath::Matrix4::from(self.rotation);
+        InstanceRaw { model }
+    }
+}
+
+// Turn `Instance`s into `InstanceRaw`s with `to_raw` first. COPY_DST lets them
+// be moved around later.
+pub fn create_instance_buffer(device: &wgpu::Device, instance_data: &[InstanceRaw]) -> wgpu::Buffer {
+    device
+        .create_buffer_mapped(instance_data.len(), wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::COPY_DST)
+        .fill_from_slice(instance_data)
+}
@@ -0,0 +1,203 @@
+// Builds bind group layouts out of what the shaders declare, so the rust side
+// can't drift away from the `layout(set=, binding=)`s in the glsl
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::reflect::Reflection;
+use crate::shader::ShaderStage;
+
+#[derive(Debug)]
+pub struct LayoutError {
+    pub set: u32,
+    pub binding: u32,
+    // (stage, variable name, type) for both declarations
+    pub first: (ShaderStage, String, wgpu::BindingType),
+    pub second: (ShaderStage, String, wgpu::BindingType),
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "set {} binding {} is {:?} {} in the {} shader, but {:?} {} in the {} shader",
+            self.set,
+            self.binding,
+            self.first.2,
+            self.first.1,
+            self.first.0,
+            self.second.2,
+            self.second.1,
+            self.second.0,
+        )
+    }
+}
+
+impl std::error::Error for LayoutError {}
+
+fn visibility(stage: ShaderStage) -> wgpu::ShaderStage {
+    match stage {
+        ShaderStage::Vertex => wgpu::ShaderStage::VERTEX,
+        ShaderStage::Fragment => wgpu::ShaderStage::FRAGMENT,
+        ShaderStage::Compute => wgpu::ShaderStage::COMPUTE,
+    }
+}
+
+// Merges the bindings of every stage into one list of bindings per set. A
+// binding used by more than one stage is visible to all of them, as long as
+// they agree on what it is. Sets nothing uses come out empty, since a
+// pipeline layout can't skip over a set.
+pub fn bind_group_layout_bindings(
+    stages: &[(ShaderStage, &Reflection)],
+) -> Result<Vec<Vec<wgpu::BindGroupLayoutBinding>>, LayoutError> {
+    let mut merged = BTreeMap::<(u32, u32), (wgpu::BindGroupLayoutBinding, ShaderStage, String)>::new();
+    for &(stage, reflection) in stages {
+        for binding in &reflection.bindings {
+            let key = (binding.set, binding.binding);
+            match merged.get_mut(&key) {
+                Some((existing, first_stage, first_name)) => {
+                    if existing.ty != binding.ty {
+                        return Err(LayoutError {
+                            set: binding.set,
+                            binding: binding.binding,
+                            first: (*first_stage, first_name.clone(), existing.ty),
+                            second: (stage, binding.name.clone(), binding.ty),
+                        });
+                    }
+                    existing.visibility |= visibility(stage);
+                }
+                None => {
+                    let layout_binding = wgpu::BindGroupLayoutBinding {
+                        binding: binding.binding,
+                        visibility: visibility(stage),
+                        ty: binding.ty,
+                    };
+                    merged.insert(key, (layout_binding, stage, binding.name.clone()));
+                }
+            }
+        }
+    }
+
+    let set_count = merged.keys().map(|&(set, _)| set + 1).max().unwrap_or(0);
+    let mut sets = (0..set_count).map(|_| Vec::new()).collect::<Vec<_>>();
+    for ((set, _), (layout_binding, _, _)) in merged {
+        sets[set as usize].push(layout_binding);
+    }
+    Ok(sets)
+}
+
+pub struct ReflectedLayout {
+    // Indexed by set
+    pub bind_group_layouts: Vec<wgpu::BindGroupLayout>,
+    pub pipeline_layout: wgpu::PipelineLayout,
+    // What `bind_group_layouts` were made from
+    pub bindings: Vec<Vec<wgpu::BindGroupLayoutBinding>>,
+}
+
+impl ReflectedLayout {
+    // Whether `stages` declare the same bindings this layout was built from,
+    // so a pipeline made with them can use it and the bind groups made for
+    // it. Shaders that changed their bindings need a new layout.
+    pub fn matches(&self, stages: &[(ShaderStage, &Reflection)]) -> Result<bool, LayoutError> {
+        let bindings = bind_group_layout_bindings(stages)?;
+        // `BindGroupLayoutBinding` isn't `PartialEq`
+        let same = |a: &wgpu::BindGroupLayoutBinding, b: &wgpu::BindGroupLayoutBinding| {
+            a.binding == b.binding && a.visibility == b.visibility && a.ty == b.ty
+        };
+        Ok(bindings.len() == self.bindings.len()
+            && bindings.iter().zip(&self.bindings).all(|(set, old_set)| {
+                set.len() == old_set.len() && set.iter().zip(old_set).all(|(a, b)| same(a, b))
+            }))
+    }
+}
+
+pub fn create_pipeline_layout(
+    device: &wgpu::Device,
+    stages: &[(ShaderStage, &Reflection)],
+) -> Result<ReflectedLayout, LayoutError> {
+    let bindings = bind_group_layout_bindings(stages)?;
+    let bind_group_layouts = bindings
+        .iter()
+        .map(|bindings| device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor { bindings }))
+        .collect::<Vec<_>>();
+
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        bind_group_layouts: &bind_group_layouts.iter().collect::<Vec<_>>(),
+    });
+
+    Ok(ReflectedLayout {
+        bind_group_layouts,
+        pipeline_layout,
+        bindings,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reflect::ShaderBinding;
+
+    fn reflection(bindings: &[(&str, u32, u32, wgpu::BindingType)]) -> Reflection {
+        Reflection {
+            inputs: Vec::new(),
+            bindings: bindings
+                .iter()
+                .map(|&(name, set, binding, ty)| ShaderBinding {
+                    name: name.to_string(),
+                    set,
+                    binding,
+                    ty,
+                })
+                .collect(),
+        }
+    }
+
+    const UNIFORMS: wgpu::BindingType = wgpu::BindingType::UniformBuffer { dynamic: false };
+
+    #[test]
+    fn bindings_shared_between_stages_are_visible_to_both() {
+        let vertex = reflection(&[("uniforms", 1, 0, UNIFORMS)]);
+        let fragment = reflection(&[
+            ("s_diffuse", 0, 1, wgpu::BindingType::Sampler),
+            ("uniforms", 1, 0, UNIFORMS),
+        ]);
+
+        let sets =
+            bind_group_layout_bindings(&[(ShaderStage::Vertex, &vertex), (ShaderStage::Fragment, &fragment)])
+                .unwrap();
+        assert_eq!(sets.len(), 2);
+        assert_eq!(sets[0].len(), 1);
+        assert_eq!(sets[0][0].binding, 1);
+        assert_eq!(sets[0][0].visibility, wgpu::ShaderStage::FRAGMENT);
+        assert_eq!(sets[1].len(), 1);
+        assert_eq!(sets[1][0].binding, 0);
+        assert_eq!(sets[1][0].visibility, wgpu::ShaderStage::VERTEX | wgpu::ShaderStage::FRAGMENT);
+        assert_eq!(sets[1][0].ty, UNIFORMS);
+    }
+
+    #[test]
+    fn unused_sets_are_empty() {
+        let vertex = reflection(&[("uniforms", 2, 0, UNIFORMS)]);
+        let sets = bind_group_layout_bindings(&[(ShaderStage::Vertex, &vertex)]).unwrap();
+        assert_eq!(sets.iter().map(Vec::len).collect::<Vec<_>>(), [0, 0, 1]);
+    }
+
+    #[test]
+    fn bindings_that_disagree_between_stages_are_errors() {
+        let vertex = reflection(&[("uniforms", 1, 0, UNIFORMS)]);
+        let fragment = reflection(&[("s_shadow", 1, 0, wgpu::BindingType::Sampler)]);
+
+        match bind_group_layout_bindings(&[(ShaderStage::Vertex, &vertex), (ShaderStage::Fragment, &fragment)]) {
+            Err(LayoutError {
+                set: 1,
+                binding: 0,
+                first: (ShaderStage::Vertex, first_name, first_ty),
+                second: (ShaderStage::Fragment, second_name, wgpu::BindingType::Sampler),
+            }) => {
+                assert_eq!(first_name, "uniforms");
+                assert_eq!(first_ty, UNIFORMS);
+                assert_eq!(second_name, "s_shadow");
+            }
+            other => panic!("expected a LayoutError for set 1 binding 0, got {:?}", other),
+        }
+    }
+}
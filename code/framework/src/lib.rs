@@ -0,0 +1,27 @@
+// Code shared between the tutorials, so every crate doesn't need its own copy
+
+// Lets the code `#[derive(VertexLayout)]` generates, which names
+// `::framework`, work inside this crate too
+extern crate self as framework;
+
+pub mod build;
+pub mod camera;
+pub mod gltf;
+pub mod instance;
+pub mod layout;
+pub mod mesh;
+pub mod obj;
+pub mod pipeline;
+pub mod pod;
+pub mod preprocessor;
+pub mod reflect;
+pub mod shader;
+pub mod shapes;
+pub mod staging;
+pub mod texture;
+pub mod validate;
+pub mod variants;
+pub mod vertex;
+
+#[cfg(feature = "hot-reload")]
+pub mod hot_reload;
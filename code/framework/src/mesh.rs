@@ -0,0 +1,175 @@
+// Geometry on the cpu side, before it's uploaded into buffers
+use crate::validate::{self, ValidationDescriptor, ValidationReport};
+use crate::vertex::VertexLayout;
+
+// The same vertex the tutorials use from tutorial5 on, so a mesh can be drawn
+// with their shaders
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, VertexLayout)]
+pub struct Vertex {
+    pub position: [f32; 3],
+    pub tex_coords: [f32; 2],
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MeshData {
+    pub vertices: Vec<Vertex>,
+    // Three per triangle, counter clockwise when looking at the front
+    pub indices: Vec<u32>,
+}
+
+impl MeshData {
+    pub fn num_indices(&self) -> u32 {
+        self.indices.len() as u32
+    }
+
+    pub fn create_vertex_buffer(&self, device: &wgpu::Device) -> wgpu::Buffer {
+        device
+            .create_buffer_mapped(self.vertices.len(), wgpu::BufferUsage::VERTEX)
+            .fill_from_slice(&self.vertices)
+    }
+
+    // The smallest index format that can point at every vertex
+    pub fn index_format(&self) -> wgpu::IndexFormat {
+        Indices::format_for_indices(&self.indices, self.vertices.len())
+    }
+
+    // The indices in `index_format`, which halves the size of the buffer for
+    // most meshes
+    pub fn create_packed_index_buffer(&self, device: &wgpu::Device) -> (wgpu::Buffer, wgpu::IndexFormat) {
+        let indices = Indices::new(&self.indices, self.vertices.len());
+        (indices.create_buffer(device), indices.format())
+    }
+
+    // Worth running before uploading anything generated or loaded from a file
+    pub fn validate(&self, desc: &ValidationDescriptor) -> ValidationReport {
+        validate::validate(&self.vertices, |v| v.position, &self.indices, desc)
+    }
+
+    pub fn create_mesh(&self, device: &wgpu::Device) -> Mesh {
+        Mesh::new(device, &self.vertices, &Indices::new(&self.indices, self.vertices.len()))
+    }
+}
+
+// Index data in whichever format the pipeline is going to read it as
+#[derive(Debug, Clone, PartialEq)]
+pub enum Indices {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+impl Indices {
+    // Picks the smallest format that can point at every one of `num_vertices`
+    pub fn new(indices: &[u32], num_vertices: usize) -> Self {
+        match Self::format_for_indices(indices, num_vertices) {
+            wgpu::IndexFormat::Uint16 => Indices::U16(indices.iter().map(|&i| i as u16).collect()),
+            wgpu::IndexFormat::Uint32 => Indices::U32(indices.to_vec()),
+        }
+    }
+
+    // 0xFFFF is left out of u16 indices, since some backends treat it as a
+    // primitive restart
+    pub fn format_for(num_vertices: usize) -> wgpu::IndexFormat {
+        if num_vertices <= u16::MAX as usize {
+            wgpu::IndexFormat::Uint16
+        } else {
+            wgpu::IndexFormat::Uint32
+        }
+    }
+
+    // Like `format_for`, but an index past the last vertex gets the format it
+    // needs too. That's a broken mesh `validate` reports, but it shouldn't
+    // wrap around to a vertex that exists when it's packed into a u16.
+    pub fn format_for_indices(indices: &[u32], num_vertices: usize) -> wgpu::IndexFormat {
+        let referenced = indices.iter().max().map_or(0, |&max| max as usize + 1);
+        Self::format_for(num_vertices.max(referenced))
+    }
+
+    pub fn format(&self) -> wgpu::IndexFormat {
+        match self {
+            Indices::U16(_) => wgpu::IndexFormat::Uint16,
+            Indices::U32(_) => wgpu::IndexFormat::Uint32,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Indices::U16(indices) => indices.len(),
+            Indices::U32(indices) => indices.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn create_buffer(&self, device: &wgpu::Device) -> wgpu::Buffer {
+        match self {
+            Indices::U16(indices) => device
+                .create_buffer_mapped(indices.len(), wgpu::BufferUsage::INDEX)
+                .fill_from_slice(indices),
+            Indices::U32(indices) => device
+                .create_buffer_mapped(indices.len(), wgpu::BufferUsage::INDEX)
+                .fill_from_slice(indices),
+        }
+    }
+}
+
+// A mesh on the gpu. `index_format` has to match the pipeline it's drawn
+// with, see `pipeline::IndexFormatPipelines`.
+pub struct Mesh {
+    pub vertex_buffer: wgpu::Buffer,
+    pub index_buffer: wgpu::Buffer,
+    pub index_format: wgpu::IndexFormat,
+    pub num_indices: u32,
+}
+
+impl Mesh {
+    // Works with any vertex type, not just `Vertex`
+    pub fn new<V: Copy + 'static>(device: &wgpu::Device, vertices: &[V], indices: &Indices) -> Self {
+        Self {
+            vertex_buffer: device
+                .create_buffer_mapped(vertices.len(), wgpu::BufferUsage::VERTEX)
+                .fill_from_slice(vertices),
+            index_buffer: indices.create_buffer(device),
+            index_format: indices.format(),
+            num_indices: indices.len() as u32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn u16_indices_stop_short_of_the_restart_index() {
+        assert_eq!(Indices::format_for(0), wgpu::IndexFormat::Uint16);
+        // The last vertex is 0xFFFE
+        assert_eq!(Indices::format_for(65535), wgpu::IndexFormat::Uint16);
+        // The last vertex would be 0xFFFF
+        assert_eq!(Indices::format_for(65536), wgpu::IndexFormat::Uint32);
+    }
+
+    #[test]
+    fn indices_are_packed_when_they_fit() {
+        let indices = Indices::new(&[0, 1, 65534], 65535);
+        assert_eq!(indices, Indices::U16(vec![0, 1, 65534]));
+
+        let indices = Indices::new(&[0, 1, 65535], 65536);
+        assert_eq!(indices, Indices::U32(vec![0, 1, 65535]));
+    }
+
+    #[test]
+    fn out_of_range_indices_are_not_truncated() {
+        // Only three vertices, but packing 65536 into a u16 would make it 0
+        let indices = Indices::new(&[0, 1, 65536], 3);
+        assert_eq!(indices, Indices::U32(vec![0, 1, 65536]));
+
+        let data = MeshData {
+            vertices: vec![Vertex { position: [0.0; 3], tex_coords: [0.0; 2] }; 3],
+            indices: vec![0, 1, 65536],
+        };
+        assert_eq!(data.index_format(), wgpu::IndexFormat::Uint32);
+    }
+}
@@ -0,0 +1,204 @@
+// Loads Wavefront OBJ files, along with the MTL files they use for materials
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::mesh::{MeshData, Vertex};
+use crate::texture::{self, Texture};
+
+#[derive(Debug)]
+pub enum ObjError {
+    Obj { path: PathBuf, error: tobj::LoadError },
+    // The OBJ was fine, but the MTL file it points at wasn't
+    Mtl { path: PathBuf, error: tobj::LoadError },
+    Texture { path: PathBuf, error: image::ImageError },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjError::Obj { path, error } => write!(f, "couldn't load {}: {}", path.display(), error),
+            ObjError::Mtl { path, error } => {
+                write!(f, "couldn't load the materials for {}: {}", path.display(), error)
+            }
+            ObjError::Texture { path, error } => write!(f, "couldn't load {}: {}", path.display(), error),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+pub struct ObjMaterial {
+    pub name: String,
+    // `map_Kd`, relative to the OBJ file
+    pub diffuse_texture: Option<PathBuf>,
+}
+
+pub struct ObjMesh {
+    pub name: String,
+    pub data: MeshData,
+    // One per vertex, if the file has them. They're kept out of `Vertex` so
+    // a mesh still fits the tutorial shaders.
+    pub normals: Option<Vec<[f32; 3]>>,
+    // Which of the model's materials this uses
+    pub material: Option<usize>,
+}
+
+// An OBJ file loaded into memory, but not onto the gpu yet
+pub struct ObjData {
+    pub meshes: Vec<ObjMesh>,
+    pub materials: Vec<ObjMaterial>,
+}
+
+pub fn load_obj<P: AsRef<Path>>(path: P) -> Result<ObjData, ObjError> {
+    let path = path.as_ref();
+    // Positions, texture coordinates and normals each have their own indices
+    // in an OBJ file, so we combine them into vertices ourselves
+    let options = tobj::LoadOptions {
+        triangulate: true,
+        single_index: false,
+        ..Default::default()
+    };
+    let (models, materials) = tobj::load_obj(path, &options).map_err(|error| ObjError::Obj {
+        path: path.to_owned(),
+        error,
+    })?;
+    let materials = materials.map_err(|error| ObjError::Mtl {
+        path: path.to_owned(),
+        error,
+    })?;
+
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    let materials = materials
+        .into_iter()
+        .map(|material| ObjMaterial {
+            diffuse_texture: if material.diffuse_texture.is_empty() {
+                None
+            } else {
+                Some(directory.join(&material.diffuse_texture))
+            },
+            name: material.name,
+        })
+        .collect();
+
+    let meshes = models.into_iter().map(|model| load_mesh(model.name, &model.mesh)).collect();
+
+    Ok(ObjData { meshes, materials })
+}
+
+fn load_mesh(name: String, mesh: &tobj::Mesh) -> ObjMesh {
+    // Faces can leave out texture coordinates or normals, in which case
+    // there's no index for them
+    let has_tex_coords = mesh.texcoord_indices.len() == mesh.indices.len();
+    let has_normals = mesh.normal_indices.len() == mesh.indices.len();
+
+    let mut data = MeshData::default();
+    let mut normals = Vec::new();
+    let mut unique = HashMap::new();
+    for (i, &position) in mesh.indices.iter().enumerate() {
+        let tex_coord = if has_tex_coords { Some(mesh.texcoord_indices[i]) } else { None };
+        let normal = if has_normals { Some(mesh.normal_indices[i]) } else { None };
+
+        // Corners that share all three indices are the same vertex
+        let index = *unique.entry((position, tex_coord, normal)).or_insert_with(|| {
+            let p = position as usize * 3;
+            let tex_coords = match tex_coord {
+                // OBJ puts v = 0 at the bottom of the image, and wgpu puts it
+                // at the top
+                Some(t) => [mesh.texcoords[t as usize * 2], 1.0 - mesh.texcoords[t as usize * 2 + 1]],
+                None => [0.0, 0.0],
+            };
+            data.vertices.push(Vertex {
+                position: [mesh.positions[p], mesh.positions[p + 1], mesh.positions[p + 2]],
+                tex_coords,
+            });
+            if let Some(n) = normal {
+                let n = n as usize * 3;
+                normals.push([mesh.normals[n], mesh.normals[n + 1], mesh.normals[n + 2]]);
+            }
+            data.vertices.len() as u32 - 1
+        });
+        data.indices.push(index);
+    }
+
+    ObjMesh {
+        name,
+        data,
+        normals: if has_normals { Some(normals) } else { None },
+        material: mesh.material_id,
+    }
+}
+
+pub struct Mesh {
+    pub name: String,
+    pub vertex_buffer: wgpu::Buffer,
+    // Bind this as its own vertex buffer, with a `Float3` per vertex
+    pub normal_buffer: Option<wgpu::Buffer>,
+    pub index_buffer: wgpu::Buffer,
+    // The pipeline drawing this mesh needs to use the same format
+    pub index_format: wgpu::IndexFormat,
+    pub num_indices: u32,
+    pub material: Option<usize>,
+}
+
+pub struct Material {
+    pub name: String,
+    pub diffuse_texture: Option<Texture>,
+}
+
+// An OBJ file with its buffers and textures on the gpu
+pub struct Model {
+    pub meshes: Vec<Mesh>,
+    pub materials: Vec<Material>,
+}
+
+impl Model {
+    pub fn load<P: AsRef<Path>>(device: &wgpu::Device, queue: &mut wgpu::Queue, path: P) -> Result<Self, ObjError> {
+        let data = load_obj(path)?;
+        Self::from_data(device, queue, &data)
+    }
+
+    pub fn from_data(device: &wgpu::Device, queue: &mut wgpu::Queue, data: &ObjData) -> Result<Self, ObjError> {
+        // Models usually expect their textures to repeat
+        let sampler = texture::sampler_descriptor(wgpu::AddressMode::Repeat);
+        let mut materials = Vec::new();
+        for material in &data.materials {
+            let diffuse_texture = match &material.diffuse_texture {
+                Some(path) => Some(Texture::load(device, queue, path, &sampler).map_err(|error| {
+                    ObjError::Texture {
+                        path: path.clone(),
+                        error,
+                    }
+                })?),
+                None => None,
+            };
+            materials.push(Material {
+                name: material.name.clone(),
+                diffuse_texture,
+            });
+        }
+
+        let meshes = data
+            .meshes
+            .iter()
+            .map(|mesh| {
+                let (index_buffer, index_format) = mesh.data.create_packed_index_buffer(device);
+                Mesh {
+                    name: mesh.name.clone(),
+                    vertex_buffer: mesh.data.create_vertex_buffer(device),
+                    normal_buffer: mesh.normals.as_ref().map(|normals| {
+                        device
+                            .create_buffer_mapped(normals.len(), wgpu::BufferUsage::VERTEX)
+                            .fill_from_slice(normals)
+                    }),
+                    index_buffer,
+                    index_format,
+                    num_indices: mesh.data.num_indices(),
+                    material: mesh.material,
+                }
+            })
+            .collect();
+
+        Ok(Self { meshes, materials })
+    }
+}
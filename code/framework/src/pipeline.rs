@@ -0,0 +1,44 @@
+// wgpu bakes the index format into the render pipeline, so a mesh with u32
+// indices can't be drawn with the pipeline we made for u16 ones
+use std::collections::hash_map::{Entry, HashMap};
+
+// One render pipeline per index format, built the first time a mesh with that
+// format shows up. Switching between meshes after that is just a lookup.
+#[derive(Default)]
+pub struct IndexFormatPipelines {
+    pipelines: HashMap<wgpu::IndexFormat, wgpu::RenderPipeline>,
+}
+
+impl IndexFormatPipelines {
+    pub fn new() -> Self {
+        Self {
+            pipelines: HashMap::new(),
+        }
+    }
+
+    // Builds the pipeline for `format` if we don't have it yet. `create` gets
+    // the format to put in the `RenderPipelineDescriptor`.
+    pub fn prepare<F, E>(&mut self, format: wgpu::IndexFormat, create: F) -> Result<&wgpu::RenderPipeline, E>
+    where
+        F: FnOnce(wgpu::IndexFormat) -> Result<wgpu::RenderPipeline, E>,
+    {
+        match self.pipelines.entry(format) {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => Ok(entry.insert(create(format)?)),
+        }
+    }
+
+    // The pipeline for a format that's already been through `prepare`
+    pub fn get(&self, format: wgpu::IndexFormat) -> Option<&wgpu::RenderPipeline> {
+        self.pipelines.get(&format)
+    }
+
+    // The formats there are pipelines for, so they can be rebuilt
+    pub fn formats(&self) -> impl Iterator<Item = wgpu::IndexFormat> + '_ {
+        self.pipelines.keys().cloned()
+    }
+
+    pub fn clear(&mut self) {
+        self.pipelines.clear();
+    }
+}
@@ -0,0 +1,41 @@
+/// Plain old data: types that are nothing but initialized bytes, so they can
+/// be copied into a gpu buffer as they are. `Copy` isn't enough for that, since
+/// a `#[repr(C)]` struct can still have padding between its fields, and
+/// reading padding bytes is undefined behavior.
+///
+/// # Safety
+///
+/// Only implement this for types that are `#[repr(C)]` (or
+/// `#[repr(transparent)]`), where every field is `Pod`, and where there are no
+/// gaps between the fields or at the end. For uniform structs that means only
+/// using f32 vectors and matrices, which don't need padding.
+pub unsafe trait Pod: Copy + 'static {}
+
+unsafe impl Pod for u8 {}
+unsafe impl Pod for u16 {}
+unsafe impl Pod for u32 {}
+unsafe impl Pod for i8 {}
+unsafe impl Pod for i16 {}
+unsafe impl Pod for i32 {}
+unsafe impl Pod for f32 {}
+
+macro_rules! impl_pod_for_arrays {
+    ($($len:expr),*) => {
+        $(unsafe impl<T: Pod> Pod for [T; $len] {})*
+    };
+}
+
+impl_pod_for_arrays!(1, 2, 3, 4, 9, 16);
+
+// cgmath's types are `#[repr(C)]` structs of their components
+unsafe impl Pod for cgmath::Vector2<f32> {}
+unsafe impl Pod for cgmath::Vector3<f32> {}
+unsafe impl Pod for cgmath::Vector4<f32> {}
+unsafe impl Pod for cgmath::Matrix2<f32> {}
+unsafe impl Pod for cgmath::Matrix3<f32> {}
+unsafe impl Pod for cgmath::Matrix4<f32> {}
+
+pub fn as_bytes<T: Pod>(data: &[T]) -> &[u8] {
+    // Safe because `T: Pod` means every byte of `data` is initialized
+    unsafe { std::slice::from_raw_parts(data.as_ptr() as *const u8, std::mem::size_of_val(data)) }
+}
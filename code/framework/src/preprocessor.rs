@@ -0,0 +1,410 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::iter::FromIterator;
+use std::path::{Path, PathBuf};
+
+// The names a shader is compiled with `#define`d. They're kept sorted, so the
+// same defines given in a different order are the same variant of a shader.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Defines(BTreeSet<String>);
+
+impl Defines {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, name: &str) -> Self {
+        self.0.insert(name.to_string());
+        self
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.0.contains(name)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl<S: Into<String>> FromIterator<S> for Defines {
+    fn from_iter<I: IntoIterator<Item = S>>(iter: I) -> Self {
+        Defines(iter.into_iter().map(Into::into).collect())
+    }
+}
+
+impl fmt::Display for Defines {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names = self.0.iter().map(String::as_str).collect::<Vec<_>>();
+        write!(f, "{}", names.join(", "))
+    }
+}
+
+// Where a line of preprocessed source originally came from
+#[derive(Debug, Clone)]
+pub struct SourceLocation {
+    pub file: PathBuf,
+    // Starts at 1, like the compiler's line numbers
+    pub line: usize,
+}
+
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.file.display(), self.line)
+    }
+}
+
+// A shader with all of its includes pasted in
+pub struct Preprocessed {
+    pub source: String,
+    // Every file that went into `source`, starting with the shader itself
+    pub files: Vec<PathBuf>,
+    lines: Vec<SourceLocation>,
+}
+
+impl Preprocessed {
+    // Maps a line number of `source` back to the file it was written in
+    pub fn location(&self, line: usize) -> Option<&SourceLocation> {
+        self.lines.get(line.checked_sub(1)?)
+    }
+}
+
+#[derive(Debug)]
+pub enum PreprocessError {
+    Io {
+        path: PathBuf,
+        error: io::Error,
+        included_from: Option<SourceLocation>,
+    },
+    // The chain of files that led back to one that was already being included
+    Cycle(Vec<PathBuf>),
+    // Includes have to look like `#include "path/to/file.glsl"`
+    BadInclude(SourceLocation),
+    // `#ifdef`, `#ifndef`, `#define` and `#undef` need a name after them
+    MissingName(SourceLocation),
+    // An `#else` or `#endif` without an `#ifdef` before it
+    UnmatchedConditional(SourceLocation),
+    // An `#ifdef` that the file never closes with an `#endif`
+    UnterminatedConditional(SourceLocation),
+}
+
+impl fmt::Display for PreprocessError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PreprocessError::Io { path, error, included_from } => {
+                write!(f, "couldn't read {}: {}", path.display(), error)?;
+                if let Some(location) = included_from {
+                    write!(f, " (included from {})", location)?;
+                }
+                Ok(())
+            }
+            PreprocessError::Cycle(chain) => {
+                let chain = chain.iter().map(|path| path.display().to_string()).collect::<Vec<_>>();
+                write!(f, "include cycle: {}", chain.join(" -> "))
+            }
+            PreprocessError::BadInclude(location) => {
+                write!(f, "{}: expected #include \"file\"", location)
+            }
+            PreprocessError::MissingName(location) => write!(f, "{}: expected a name", location),
+            PreprocessError::UnmatchedConditional(location) => {
+                write!(f, "{}: no #ifdef or #ifndef to go with this", location)
+            }
+            PreprocessError::UnterminatedConditional(location) => {
+                write!(f, "{}: missing an #endif for this", location)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PreprocessError {}
+
+// Reads the shader at `path` and replaces every `#include "file"` with the
+// contents of that file. Includes are relative to the file they're written
+// in. A file is only pasted in once, so two shared files can both include a
+// third one without it getting defined twice.
+pub fn preprocess(path: &Path) -> Result<Preprocessed, PreprocessError> {
+    preprocess_with_defines(path, &Defines::new())
+}
+
+// Same as `preprocess`, but also keeps or drops the lines between `#ifdef`,
+// `#ifndef`, `#else` and `#endif` depending on what's defined. We do this
+// ourselves instead of leaving it to the compiler, since wgsl doesn't have a
+// preprocessor. `#define`s in the shader count too, and are left in the source
+// so glsl can still use their values.
+pub fn preprocess_with_defines(path: &Path, defines: &Defines) -> Result<Preprocessed, PreprocessError> {
+    let mut preprocessor = Preprocessor {
+        source: String::new(),
+        files: Vec::new(),
+        lines: Vec::new(),
+        stack: Vec::new(),
+        included: Vec::new(),
+        defines: defines.0.clone(),
+    };
+    preprocessor.include(path, None)?;
+
+    Ok(Preprocessed {
+        source: preprocessor.source,
+        files: preprocessor.files,
+        lines: preprocessor.lines,
+    })
+}
+
+struct Preprocessor {
+    source: String,
+    files: Vec<PathBuf>,
+    lines: Vec<SourceLocation>,
+    // The files we're in the middle of including, to catch cycles
+    stack: Vec<PathBuf>,
+    // Canonical paths of everything pasted in so far
+    included: Vec<PathBuf>,
+    defines: BTreeSet<String>,
+}
+
+// An `#ifdef` or `#ifndef` we're inside of
+struct Conditional {
+    start: SourceLocation,
+    // Whether the lines we're on get kept, ignoring any outer conditionals
+    active: bool,
+    seen_else: bool,
+}
+
+impl Preprocessor {
+    fn include(&mut self, path: &Path, included_from: Option<SourceLocation>) -> Result<(), PreprocessError> {
+        let io_error = |error| PreprocessError::Io {
+            path: path.to_path_buf(),
+            error,
+            included_from: included_from.clone(),
+        };
+
+        // Two different relative paths can point to the same file, so we
+        // compare canonical paths
+        let canonical = path.canonicalize().map_err(io_error)?;
+        if let Some(start) = self.stack.iter().position(|p| *p == canonical) {
+            let mut chain = self.stack[start..].to_vec();
+            chain.push(canonical);
+            return Err(PreprocessError::Cycle(chain));
+        }
+        if self.included.contains(&canonical) {
+            return Ok(());
+        }
+
+        let src = fs::read_to_string(path).map_err(io_error)?;
+        self.stack.push(canonical.clone());
+        self.included.push(canonical);
+        self.files.push(path.to_path_buf());
+
+        // Conditionals can't start in one file and end in another
+        let mut conditionals = Vec::<Conditional>::new();
+        for (i, line) in src.lines().enumerate() {
+            let location = SourceLocation {
+                file: path.to_path_buf(),
+                line: i + 1,
+            };
+            let active = conditionals.iter().all(|conditional| conditional.active);
+
+            let mut words = line.split_whitespace();
+            let directive = words.next().unwrap_or("");
+            let name = words.next();
+            let name = || name.ok_or_else(|| PreprocessError::MissingName(location.clone()));
+
+            match directive {
+                "#ifdef" | "#ifndef" => {
+                    let defined = self.defines.contains(name()?);
+                    conditionals.push(Conditional {
+                        start: location,
+                        active: defined == (directive == "#ifdef"),
+                        seen_else: false,
+                    });
+                }
+                "#else" => match conditionals.last_mut() {
+                    Some(conditional) if !conditional.seen_else => {
+                        conditional.active = !conditional.active;
+                        conditional.seen_else = true;
+                    }
+                    _ => return Err(PreprocessError::UnmatchedConditional(location)),
+                },
+                "#endif" => {
+                    if conditionals.pop().is_none() {
+                        return Err(PreprocessError::UnmatchedConditional(location));
+                    }
+                }
+                _ if !active => {}
+                _ if line.trim().starts_with("#include") => {
+                    let include = line
+                        .trim()
+                        .trim_start_matches("#include")
+                        .trim()
+                        .strip_prefix('"')
+                        .and_then(|rest| rest.strip_suffix('"'))
+                        .ok_or_else(|| PreprocessError::BadInclude(location.clone()))?;
+                    let include_path = path.parent().unwrap_or_else(|| Path::new("")).join(include);
+                    self.include(&include_path, Some(location))?;
+                }
+                _ => {
+                    match directive {
+                        "#define" => {
+                            self.defines.insert(name()?.to_string());
+                        }
+                        "#undef" => {
+                            self.defines.remove(name()?);
+                        }
+                        _ => {}
+                    }
+                    self.source.push_str(line);
+                    self.source.push('\n');
+                    self.lines.push(location);
+                }
+            }
+        }
+
+        if let Some(conditional) = conditionals.pop() {
+            return Err(PreprocessError::UnterminatedConditional(conditional.start));
+        }
+
+        self.stack.pop();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    // A directory of shader files for one test, deleted when it's done
+    struct Fixture(PathBuf);
+
+    impl Fixture {
+        fn new(test: &str, files: &[(&str, &str)]) -> Self {
+            let dir = env::temp_dir().join(format!("framework-preprocessor-{}-{}", test, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            for (name, contents) in files {
+                let path = dir.join(name);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, contents).unwrap();
+            }
+            Fixture(dir)
+        }
+
+        fn path(&self, name: &str) -> PathBuf {
+            self.0.join(name)
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn lines(preprocessed: &Preprocessed) -> Vec<&str> {
+        preprocessed.source.lines().collect()
+    }
+
+    #[test]
+    fn include_cycles_are_errors() {
+        let fixture = Fixture::new("cycle", &[
+            ("a.glsl", "#include \"b.glsl\"\n"),
+            ("b.glsl", "#include \"a.glsl\"\n"),
+        ]);
+        let a = fixture.path("a.glsl").canonicalize().unwrap();
+        let b = fixture.path("b.glsl").canonicalize().unwrap();
+
+        let error = preprocess(&fixture.path("a.glsl")).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            format!("include cycle: {} -> {} -> {}", a.display(), b.display(), a.display()),
+        );
+    }
+
+    #[test]
+    fn included_lines_map_back_to_their_file() {
+        let fixture = Fixture::new("location", &[
+            ("shader.vert", "#version 450\n#include \"common/camera.glsl\"\nvoid main() {}\n"),
+            ("common/camera.glsl", "// camera\nlayout(set=1, binding=0) uniform Uniforms { mat4 u_view_proj; };\n"),
+        ]);
+        let shader = fixture.path("shader.vert");
+        let camera = fixture.path("common/camera.glsl");
+
+        let preprocessed = preprocess(&shader).unwrap();
+        assert_eq!(preprocessed.files, [shader.clone(), camera.clone()]);
+        assert_eq!(lines(&preprocessed)[2], "layout(set=1, binding=0) uniform Uniforms { mat4 u_view_proj; };");
+
+        let location = preprocessed.location(3).unwrap();
+        assert_eq!((&location.file, location.line), (&camera, 2));
+        let location = preprocessed.location(4).unwrap();
+        assert_eq!((&location.file, location.line), (&shader, 3));
+        assert!(preprocessed.location(0).is_none());
+        assert!(preprocessed.location(5).is_none());
+    }
+
+    #[test]
+    fn files_are_only_included_once() {
+        let fixture = Fixture::new("once", &[
+            ("shader.vert", "#include \"a.glsl\"\n#include \"b.glsl\"\n"),
+            ("a.glsl", "#include \"common.glsl\"\na\n"),
+            ("b.glsl", "#include \"./common.glsl\"\nb\n"),
+            ("common.glsl", "common\n"),
+        ]);
+        let preprocessed = preprocess(&fixture.path("shader.vert")).unwrap();
+        assert_eq!(lines(&preprocessed), ["common", "a", "b"]);
+    }
+
+    #[test]
+    fn conditionals_follow_the_defines() {
+        let fixture = Fixture::new("conditionals", &[(
+            "shader.frag",
+            "#ifdef COLOR\ncolor\n#else\nwhite\n#endif\n\
+             #ifndef COLOR\nno color\n#endif\n\
+             #define SHADED\n#ifdef SHADED\nshaded\n#endif\n\
+             #undef SHADED\n#ifdef SHADED\nstill shaded\n#endif\n",
+        )]);
+        let path = fixture.path("shader.frag");
+
+        let plain = preprocess(&path).unwrap();
+        assert_eq!(lines(&plain), ["white", "no color", "#define SHADED", "shaded", "#undef SHADED"]);
+
+        let colored = preprocess_with_defines(&path, &Defines::new().with("COLOR")).unwrap();
+        assert_eq!(lines(&colored), ["color", "#define SHADED", "shaded", "#undef SHADED"]);
+        // Lines that were dropped don't throw off where the rest came from
+        assert_eq!(colored.location(2).unwrap().line, 9);
+    }
+
+    #[test]
+    fn malformed_directives_are_errors() {
+        let fixture = Fixture::new("malformed", &[
+            ("unmatched.vert", "#endif\n"),
+            ("unterminated.vert", "void main() {}\n#ifdef COLOR\n"),
+            ("unnamed.vert", "#ifdef\n#endif\n"),
+            ("bad_include.vert", "#include <camera.glsl>\n"),
+            ("missing_include.vert", "\n#include \"missing.glsl\"\n"),
+        ]);
+        let error = |name| preprocess(&fixture.path(name)).err().unwrap();
+
+        match error("unmatched.vert") {
+            PreprocessError::UnmatchedConditional(location) => assert_eq!(location.line, 1),
+            e => panic!("expected an unmatched conditional, got {:?}", e),
+        }
+        match error("unterminated.vert") {
+            PreprocessError::UnterminatedConditional(location) => assert_eq!(location.line, 2),
+            e => panic!("expected an unterminated conditional, got {:?}", e),
+        }
+        match error("unnamed.vert") {
+            PreprocessError::MissingName(location) => assert_eq!(location.line, 1),
+            e => panic!("expected a missing name, got {:?}", e),
+        }
+        match error("bad_include.vert") {
+            PreprocessError::BadInclude(location) => assert_eq!(location.line, 1),
+            e => panic!("expected a bad include, got {:?}", e),
+        }
+        match error("missing_include.vert") {
+            PreprocessError::Io { path, included_from: Some(location), .. } => {
+                assert_eq!(path, fixture.path("missing.glsl"));
+                assert_eq!((location.file, location.line), (fixture.path("missing_include.vert"), 2));
+            }
+            e => panic!("expected a missing file, got {:?}", e),
+        }
+    }
+}
@@ -0,0 +1,811 @@
+// Just enough of a spirv parser to find out what a shader expects from the
+// pipeline, so we can check it against what the rust code actually gives it
+use std::collections::HashMap;
+use std::fmt;
+
+const MAGIC_NUMBER: u32 = 0x0723_0203;
+
+mod op {
+    pub const NAME: u32 = 5;
+    pub const ENTRY_POINT: u32 = 15;
+    pub const TYPE_BOOL: u32 = 20;
+    pub const TYPE_INT: u32 = 21;
+    pub const TYPE_FLOAT: u32 = 22;
+    pub const TYPE_VECTOR: u32 = 23;
+    pub const TYPE_MATRIX: u32 = 24;
+    pub const TYPE_IMAGE: u32 = 25;
+    pub const TYPE_SAMPLER: u32 = 26;
+    pub const TYPE_SAMPLED_IMAGE: u32 = 27;
+    pub const TYPE_ARRAY: u32 = 28;
+    pub const TYPE_RUNTIME_ARRAY: u32 = 29;
+    pub const TYPE_STRUCT: u32 = 30;
+    pub const TYPE_POINTER: u32 = 32;
+    pub const CONSTANT: u32 = 43;
+    pub const VARIABLE: u32 = 59;
+    pub const DECORATE: u32 = 71;
+    pub const MEMBER_DECORATE: u32 = 72;
+}
+
+const EXECUTION_MODEL_VERTEX: u32 = 0;
+
+mod decoration {
+    pub const BUFFER_BLOCK: u32 = 3;
+    pub const BUILT_IN: u32 = 11;
+    pub const NON_WRITABLE: u32 = 24;
+    pub const LOCATION: u32 = 30;
+    pub const BINDING: u32 = 33;
+    pub const DESCRIPTOR_SET: u32 = 34;
+}
+
+mod storage_class {
+    pub const UNIFORM_CONSTANT: u32 = 0;
+    pub const INPUT: u32 = 1;
+    pub const UNIFORM: u32 = 2;
+    pub const STORAGE_BUFFER: u32 = 12;
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ScalarKind {
+    Float,
+    Sint,
+    Uint,
+    Bool,
+}
+
+// One location's worth of a vertex shader input. A `mat4` takes up four
+// locations, one per column, so it turns into four of these.
+#[derive(Debug, Clone)]
+pub struct ShaderInput {
+    pub name: String,
+    pub location: u32,
+    pub kind: ScalarKind,
+    pub components: u32,
+    // The variable this is part of, and where it starts
+    pub variable_type: String,
+    pub first_location: u32,
+}
+
+impl ShaderInput {
+    pub fn glsl_type(&self) -> String {
+        glsl_type_name(self.kind, self.components)
+    }
+}
+
+impl fmt::Display for ShaderInput {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.location == self.first_location && self.variable_type == self.glsl_type() {
+            write!(f, "{} {} at location {}", self.variable_type, self.name, self.location)
+        } else {
+            write!(
+                f,
+                "{} {} at location {} (part of {} {} starting at location {})",
+                self.glsl_type(),
+                self.name,
+                self.location,
+                self.variable_type,
+                self.name,
+                self.first_location,
+            )
+        }
+    }
+}
+
+// A uniform buffer, storage buffer, texture or sampler the shader reads
+#[derive(Debug, Clone)]
+pub struct ShaderBinding {
+    pub name: String,
+    pub set: u32,
+    pub binding: u32,
+    // Shaders can't say whether a buffer is dynamic, so `dynamic` is always false
+    pub ty: wgpu::BindingType,
+}
+
+#[derive(Debug)]
+pub struct ReflectError(pub String);
+
+impl fmt::Display for ReflectError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "couldn't reflect spirv: {}", self.0)
+    }
+}
+
+impl std::error::Error for ReflectError {}
+
+#[derive(Debug, Clone)]
+enum Type {
+    Bool,
+    Int { signed: bool },
+    Float,
+    Vector { component: u32, count: u32 },
+    Matrix { column: u32, count: u32 },
+    Array { element: u32, length: u32 },
+    RuntimeArray,
+    Struct { members: u32 },
+    Image { dim: u32, arrayed: bool, multisampled: bool, sampled: u32 },
+    Sampler,
+    SampledImage,
+    Pointer { pointee: u32 },
+}
+
+pub struct Reflection {
+    pub inputs: Vec<ShaderInput>,
+    pub bindings: Vec<ShaderBinding>,
+}
+
+struct Module {
+    names: HashMap<u32, String>,
+    decorations: HashMap<u32, Vec<(u32, Vec<u32>)>>,
+    // struct id to (member, decoration)
+    member_decorations: HashMap<u32, Vec<(u32, u32)>>,
+    types: HashMap<u32, Type>,
+    constants: HashMap<u32, u32>,
+    // (result type, id, storage class)
+    variables: Vec<(u32, u32, u32)>,
+    // The inputs and outputs of the vertex entry point. A wgsl file can have
+    // a vertex and fragment shader in one module, and we only want the
+    // inputs that come from vertex buffers.
+    vertex_interface: Option<Vec<u32>>,
+}
+
+impl Module {
+    fn parse(spirv: &[u32]) -> Result<Self, ReflectError> {
+        if spirv.len() < 5 || spirv[0] != MAGIC_NUMBER {
+            return Err(ReflectError("missing the spirv header".to_string()));
+        }
+
+        let mut module = Module {
+            names: HashMap::new(),
+            decorations: HashMap::new(),
+            member_decorations: HashMap::new(),
+            types: HashMap::new(),
+            constants: HashMap::new(),
+            variables: Vec::new(),
+            vertex_interface: None,
+        };
+
+        let mut words = &spirv[5..];
+        while !words.is_empty() {
+            let word_count = (words[0] >> 16) as usize;
+            let opcode = words[0] & 0xffff;
+            if word_count == 0 || word_count > words.len() {
+                return Err(ReflectError(format!("instruction {} has a bad length", opcode)));
+            }
+            let operands = &words[1..word_count];
+            words = &words[word_count..];
+
+            let operand = |i: usize| {
+                operands
+                    .get(i)
+                    .copied()
+                    .ok_or_else(|| ReflectError(format!("instruction {} is too short", opcode)))
+            };
+
+            match opcode {
+                op::NAME => {
+                    module.names.insert(operand(0)?, parse_string(&operands[1..]));
+                }
+                op::ENTRY_POINT if operand(0)? == EXECUTION_MODEL_VERTEX => {
+                    // The interface comes after the name, which ends with the
+                    // first word that has a nul byte in it
+                    let name_words = operands
+                        .get(2..)
+                        .unwrap_or(&[])
+                        .iter()
+                        .position(|word| word.to_le_bytes().contains(&0))
+                        .ok_or_else(|| ReflectError("entry point name isn't terminated".to_string()))?
+                        + 1;
+                    let interface = module.vertex_interface.get_or_insert_with(Vec::new);
+                    interface.extend_from_slice(&operands[2 + name_words..]);
+                }
+                op::DECORATE => {
+                    module
+                        .decorations
+                        .entry(operand(0)?)
+                        .or_default()
+                        .push((operand(1)?, operands[2..].to_vec()));
+                }
+                op::MEMBER_DECORATE => {
+                    module
+                        .member_decorations
+                        .entry(operand(0)?)
+                        .or_default()
+                        .push((operand(1)?, operand(2)?));
+                }
+                op::TYPE_BOOL => {
+                    module.types.insert(operand(0)?, Type::Bool);
+                }
+                op::TYPE_INT => {
+                    module.types.insert(operand(0)?, Type::Int { signed: operand(2)? != 0 });
+                }
+                op::TYPE_FLOAT => {
+                    module.types.insert(operand(0)?, Type::Float);
+                }
+                op::TYPE_VECTOR => {
+                    let ty = Type::Vector { component: operand(1)?, count: operand(2)? };
+                    module.types.insert(operand(0)?, ty);
+                }
+                op::TYPE_MATRIX => {
+                    let ty = Type::Matrix { column: operand(1)?, count: operand(2)? };
+                    module.types.insert(operand(0)?, ty);
+                }
+                op::TYPE_ARRAY => {
+                    // The length is an id of a constant, which comes before the array type
+                    let length = module.constants.get(&operand(2)?).copied().unwrap_or(1);
+                    let ty = Type::Array { element: operand(1)?, length };
+                    module.types.insert(operand(0)?, ty);
+                }
+                op::TYPE_IMAGE => {
+                    let ty = Type::Image {
+                        dim: operand(2)?,
+                        arrayed: operand(4)? != 0,
+                        multisampled: operand(5)? != 0,
+                        sampled: operand(6)?,
+                    };
+                    module.types.insert(operand(0)?, ty);
+                }
+                op::TYPE_SAMPLER => {
+                    module.types.insert(operand(0)?, Type::Sampler);
+                }
+                op::TYPE_SAMPLED_IMAGE => {
+                    module.types.insert(operand(0)?, Type::SampledImage);
+                }
+                op::TYPE_RUNTIME_ARRAY => {
+                    module.types.insert(operand(0)?, Type::RuntimeArray);
+                }
+                op::TYPE_STRUCT => {
+                    // Everything after the id is a member type
+                    let id = operand(0)?;
+                    module.types.insert(id, Type::Struct { members: operands.len() as u32 - 1 });
+                }
+                op::TYPE_POINTER => {
+                    module.types.insert(operand(0)?, Type::Pointer { pointee: operand(2)? });
+                }
+                op::CONSTANT => {
+                    module.constants.insert(operand(1)?, operand(2)?);
+                }
+                op::VARIABLE => {
+                    module.variables.push((operand(0)?, operand(1)?, operand(2)?));
+                }
+                _ => {}
+            }
+        }
+
+        Ok(module)
+    }
+
+    fn decoration(&self, id: u32, decoration: u32) -> Option<&[u32]> {
+        self.decorations
+            .get(&id)?
+            .iter()
+            .find(|(d, _)| *d == decoration)
+            .map(|(_, operands)| &operands[..])
+    }
+
+    fn get_type(&self, id: u32) -> Result<&Type, ReflectError> {
+        self.types
+            .get(&id)
+            .ok_or_else(|| ReflectError(format!("unknown type %{}", id)))
+    }
+
+    // The scalar kind and component count of a scalar or vector type
+    fn numeric(&self, id: u32) -> Result<(ScalarKind, u32), ReflectError> {
+        match self.get_type(id)? {
+            Type::Bool => Ok((ScalarKind::Bool, 1)),
+            Type::Int { signed: true } => Ok((ScalarKind::Sint, 1)),
+            Type::Int { signed: false } => Ok((ScalarKind::Uint, 1)),
+            Type::Float => Ok((ScalarKind::Float, 1)),
+            Type::Vector { component, count } => Ok((self.numeric(*component)?.0, *count)),
+            ty => Err(ReflectError(format!("expected a scalar or vector, found {:?}", ty))),
+        }
+    }
+
+    fn glsl_type(&self, id: u32) -> Result<String, ReflectError> {
+        Ok(match self.get_type(id)? {
+            Type::Matrix { column, count } => {
+                let (_, rows) = self.numeric(*column)?;
+                if rows == *count {
+                    format!("mat{}", count)
+                } else {
+                    format!("mat{}x{}", count, rows)
+                }
+            }
+            Type::Array { element, length } => format!("{}[{}]", self.glsl_type(*element)?, length),
+            Type::Struct { .. } => "struct".to_string(),
+            _ => {
+                let (kind, components) = self.numeric(id)?;
+                glsl_type_name(kind, components)
+            }
+        })
+    }
+
+    // Splits a type into the per location pieces it occupies
+    fn locations(&self, id: u32, pieces: &mut Vec<(ScalarKind, u32)>) -> Result<(), ReflectError> {
+        match self.get_type(id)? {
+            Type::Matrix { column, count } => {
+                for _ in 0..*count {
+                    self.locations(*column, pieces)?;
+                }
+            }
+            Type::Array { element, length } => {
+                for _ in 0..*length {
+                    self.locations(*element, pieces)?;
+                }
+            }
+            _ => pieces.push(self.numeric(id)?),
+        }
+        Ok(())
+    }
+
+    fn inputs(&self) -> Result<Vec<ShaderInput>, ReflectError> {
+        let mut inputs = Vec::new();
+        for &(result_type, id, storage) in &self.variables {
+            // Built ins like gl_VertexIndex don't come from vertex buffers
+            if storage != storage_class::INPUT || self.decoration(id, decoration::BUILT_IN).is_some() {
+                continue;
+            }
+            if let Some(interface) = &self.vertex_interface {
+                if !interface.contains(&id) {
+                    continue;
+                }
+            }
+            let first_location = match self.decoration(id, decoration::LOCATION) {
+                Some(operands) if !operands.is_empty() => operands[0],
+                _ => continue,
+            };
+            let ty = match self.get_type(result_type)? {
+                Type::Pointer { pointee } => *pointee,
+                _ => return Err(ReflectError(format!("variable %{} isn't a pointer", id))),
+            };
+
+            let name = self.names.get(&id).cloned().unwrap_or_else(|| format!("%{}", id));
+            let variable_type = self.glsl_type(ty)?;
+            let mut pieces = Vec::new();
+            self.locations(ty, &mut pieces)?;
+            for (i, (kind, components)) in pieces.into_iter().enumerate() {
+                inputs.push(ShaderInput {
+                    name: name.clone(),
+                    location: first_location + i as u32,
+                    kind,
+                    components,
+                    variable_type: variable_type.clone(),
+                    first_location,
+                });
+            }
+        }
+        inputs.sort_by_key(|input| input.location);
+        Ok(inputs)
+    }
+
+    fn bindings(&self) -> Result<Vec<ShaderBinding>, ReflectError> {
+        let mut bindings = Vec::new();
+        for &(result_type, id, storage) in &self.variables {
+            let is_resource = matches!(
+                storage,
+                storage_class::UNIFORM_CONSTANT | storage_class::UNIFORM | storage_class::STORAGE_BUFFER
+            );
+            if !is_resource {
+                continue;
+            }
+
+            let name = self.names.get(&id).cloned().unwrap_or_else(|| format!("%{}", id));
+            let decoration = |d| {
+                self.decoration(id, d)
+                    .and_then(|operands| operands.first().copied())
+                    .ok_or_else(|| ReflectError(format!("{} doesn't have a set and binding", name)))
+            };
+            let set = decoration(decoration::DESCRIPTOR_SET)?;
+            let binding = decoration(decoration::BINDING)?;
+
+            let pointee = match self.get_type(result_type)? {
+                Type::Pointer { pointee } => *pointee,
+                _ => return Err(ReflectError(format!("variable {} isn't a pointer", name))),
+            };
+            let ty = self.binding_type(id, pointee, storage, &name)?;
+            bindings.push(ShaderBinding { name, set, binding, ty });
+        }
+        bindings.sort_by_key(|binding| (binding.set, binding.binding));
+        Ok(bindings)
+    }
+
+    fn binding_type(&self, variable: u32, ty: u32, storage: u32, name: &str) -> Result<wgpu::BindingType, ReflectError> {
+        match self.get_type(ty)? {
+            Type::Struct { members } => {
+                // Older spirv marks storage buffers with BufferBlock instead of a storage class
+                let is_storage = storage == storage_class::STORAGE_BUFFER
+                    || self.decoration(ty, decoration::BUFFER_BLOCK).is_some();
+                if !is_storage {
+                    return Ok(wgpu::BindingType::UniformBuffer { dynamic: false });
+                }
+
+                // `readonly buffer` puts NonWritable on every member, or on the variable itself
+                let member_decorations = self.member_decorations.get(&ty).map(|d| &d[..]).unwrap_or(&[]);
+                let readonly = self.decoration(variable, decoration::NON_WRITABLE).is_some()
+                    || (0..*members).all(|member| member_decorations.contains(&(member, decoration::NON_WRITABLE)));
+                Ok(wgpu::BindingType::StorageBuffer { dynamic: false, readonly })
+            }
+            Type::Sampler => Ok(wgpu::BindingType::Sampler),
+            &Type::Image { dim, arrayed, multisampled, sampled } => {
+                let dimension = match (dim, arrayed) {
+                    (0, false) => wgpu::TextureViewDimension::D1,
+                    (1, false) => wgpu::TextureViewDimension::D2,
+                    (1, true) => wgpu::TextureViewDimension::D2Array,
+                    (2, false) => wgpu::TextureViewDimension::D3,
+                    (3, false) => wgpu::TextureViewDimension::Cube,
+                    (3, true) => wgpu::TextureViewDimension::CubeArray,
+                    _ => return Err(ReflectError(format!("{} has a texture dimension wgpu doesn't support", name))),
+                };
+                // 2 means the image is used for storage rather than sampling
+                if sampled == 2 {
+                    Ok(wgpu::BindingType::StorageTexture { dimension })
+                } else {
+                    Ok(wgpu::BindingType::SampledTexture { multisampled, dimension })
+                }
+            }
+            Type::SampledImage => Err(ReflectError(format!(
+                "{} is a combined image sampler, wgpu needs a separate texture and sampler",
+                name
+            ))),
+            Type::Array { .. } | Type::RuntimeArray => {
+                Err(ReflectError(format!("{} is an array of bindings, which wgpu doesn't support", name)))
+            }
+            ty => Err(ReflectError(format!("{} has a type that can't be bound: {:?}", name, ty))),
+        }
+    }
+}
+
+pub fn reflect(spirv: &[u32]) -> Result<Reflection, ReflectError> {
+    let module = Module::parse(spirv)?;
+    Ok(Reflection {
+        inputs: module.inputs()?,
+        bindings: module.bindings()?,
+    })
+}
+
+// Strings are packed four bytes to a word and end with a nul
+fn parse_string(words: &[u32]) -> String {
+    let bytes = words
+        .iter()
+        .flat_map(|word| word.to_le_bytes().to_vec())
+        .take_while(|&byte| byte != 0)
+        .collect::<Vec<_>>();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+fn glsl_type_name(kind: ScalarKind, components: u32) -> String {
+    let (scalar, prefix) = match kind {
+        ScalarKind::Float => ("float", ""),
+        ScalarKind::Sint => ("int", "i"),
+        ScalarKind::Uint => ("uint", "u"),
+        ScalarKind::Bool => ("bool", "b"),
+    };
+    if components == 1 {
+        scalar.to_string()
+    } else {
+        format!("{}vec{}", prefix, components)
+    }
+}
+
+// What a vertex format looks like from the shader's side
+pub fn vertex_format_type(format: wgpu::VertexFormat) -> (ScalarKind, u32) {
+    use wgpu::VertexFormat::*;
+    match format {
+        Uchar2 | Ushort2 | Uint2 => (ScalarKind::Uint, 2),
+        Uchar4 | Ushort4 | Uint4 => (ScalarKind::Uint, 4),
+        Uint => (ScalarKind::Uint, 1),
+        Uint3 => (ScalarKind::Uint, 3),
+        Char2 | Short2 | Int2 => (ScalarKind::Sint, 2),
+        Char4 | Short4 | Int4 => (ScalarKind::Sint, 4),
+        Int => (ScalarKind::Sint, 1),
+        Int3 => (ScalarKind::Sint, 3),
+        // Normalized integers show up in the shader as floats
+        Uchar2Norm | Char2Norm | Ushort2Norm | Short2Norm | Half2 | Float2 => (ScalarKind::Float, 2),
+        Uchar4Norm | Char4Norm | Ushort4Norm | Short4Norm | Half4 | Float4 => (ScalarKind::Float, 4),
+        Float => (ScalarKind::Float, 1),
+        Float3 => (ScalarKind::Float, 3),
+    }
+}
+
+#[derive(Debug)]
+pub enum VertexInputMismatch {
+    // The shader reads a location no vertex buffer provides
+    Missing(ShaderInput),
+    Incompatible {
+        input: ShaderInput,
+        buffer: usize,
+        format: wgpu::VertexFormat,
+    },
+    // Two attributes claim the same location
+    Duplicate {
+        location: u32,
+        buffers: (usize, usize),
+    },
+}
+
+impl fmt::Display for VertexInputMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VertexInputMismatch::Missing(input) => {
+                write!(f, "shader input {} has no vertex attribute", input)
+            }
+            VertexInputMismatch::Incompatible { input, buffer, format } => write!(
+                f,
+                "shader input {} doesn't match the {:?} attribute in vertex buffer {}",
+                input, format, buffer,
+            ),
+            VertexInputMismatch::Duplicate { location, buffers } => write!(
+                f,
+                "location {} is used by vertex buffer {} and vertex buffer {}",
+                location, buffers.0, buffers.1,
+            ),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct VertexLayoutError {
+    pub shader: String,
+    pub mismatches: Vec<VertexInputMismatch>,
+}
+
+impl fmt::Display for VertexLayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "the vertex buffers don't match the inputs of {}", self.shader)?;
+        for mismatch in &self.mismatches {
+            write!(f, "\n  {}", mismatch)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for VertexLayoutError {}
+
+// Checks that every input of the vertex shader has an attribute in
+// `vertex_buffers` with a format the shader can read. Attributes the shader
+// doesn't use are fine. The buffer can leave off components of a vec4, which
+// get filled in with 0, 0, 0, 1. That's usually what you want for positions.
+pub fn check_vertex_buffers(
+    shader: &str,
+    reflection: &Reflection,
+    vertex_buffers: &[wgpu::VertexBufferDescriptor],
+) -> Result<(), VertexLayoutError> {
+    let mut mismatches = Vec::new();
+
+    let mut attributes = HashMap::new();
+    for (buffer, descriptor) in vertex_buffers.iter().enumerate() {
+        for attribute in descriptor.attributes {
+            if let Some((other, _)) = attributes.insert(attribute.shader_location, (buffer, attribute.format)) {
+                mismatches.push(VertexInputMismatch::Duplicate {
+                    location: attribute.shader_location,
+                    buffers: (other, buffer),
+                });
+            }
+        }
+    }
+
+    for input in &reflection.inputs {
+        match attributes.get(&input.location) {
+            None => mismatches.push(VertexInputMismatch::Missing(input.clone())),
+            Some(&(buffer, format)) => {
+                let (kind, components) = vertex_format_type(format);
+                let components_fit = components == input.components || (input.components == 4 && components < 4);
+                if kind != input.kind || !components_fit {
+                    mismatches.push(VertexInputMismatch::Incompatible {
+                        input: input.clone(),
+                        buffer,
+                        format,
+                    });
+                }
+            }
+        }
+    }
+
+    if mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(VertexLayoutError {
+            shader: shader.to_string(),
+            mismatches,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shader;
+
+    // WGSL keeps both stages in one module
+    const SHADER: &str = r#"
+[[block]]
+struct Uniforms {
+    view_proj: mat4x4<f32>;
+};
+[[group(1), binding(0)]] var<uniform> uniforms: Uniforms;
+
+[[location(0)]] var<in> a_position: vec3<f32>;
+[[location(1)]] var<in> a_tex_coords: vec2<f32>;
+[[location(0)]] var<out> v_tex_coords: vec2<f32>;
+[[builtin(position)]] var<out> out_position: vec4<f32>;
+
+[[stage(vertex)]]
+fn main() {
+    v_tex_coords = a_tex_coords;
+    out_position = uniforms.view_proj * vec4<f32>(a_position, 1.0);
+}
+
+[[group(0), binding(0)]] var t_diffuse: texture_2d<f32>;
+[[group(0), binding(1)]] var s_diffuse: sampler;
+[[location(0)]] var<in> v_tex_coords: vec2<f32>;
+[[location(0)]] var<out> f_color: vec4<f32>;
+
+[[stage(fragment)]]
+fn main() {
+    f_color = textureSample(t_diffuse, s_diffuse, v_tex_coords);
+}
+"#;
+
+    fn reflect_shader() -> Reflection {
+        let spirv = shader::compile_wgsl("test.wgsl", SHADER, None).unwrap();
+        reflect(&spirv).unwrap()
+    }
+
+    fn instruction(opcode: u32, operands: &[u32]) -> Vec<u32> {
+        let mut words = vec![(operands.len() as u32 + 1) << 16 | opcode];
+        words.extend_from_slice(operands);
+        words
+    }
+
+    fn module(instructions: &[Vec<u32>]) -> Vec<u32> {
+        let mut words = vec![MAGIC_NUMBER, 0x0001_0000, 0, 16, 0];
+        for instruction in instructions {
+            words.extend_from_slice(instruction);
+        }
+        words
+    }
+
+    // `layout(location=2) in mat4 a_model;`, written out by hand
+    fn matrix_input() -> Vec<u32> {
+        let name = u32::from_le_bytes(*b"a_mo");
+        let name_end = u32::from_le_bytes(*b"del\0");
+        module(&[
+            instruction(op::NAME, &[5, name, name_end]),
+            instruction(op::DECORATE, &[5, decoration::LOCATION, 2]),
+            instruction(op::TYPE_FLOAT, &[1, 32]),
+            instruction(op::TYPE_VECTOR, &[2, 1, 4]),
+            instruction(op::TYPE_MATRIX, &[3, 2, 4]),
+            instruction(op::TYPE_POINTER, &[4, storage_class::INPUT, 3]),
+            instruction(op::VARIABLE, &[4, 5, storage_class::INPUT]),
+        ])
+    }
+
+    const VERTEX_ATTRIBUTES: &[wgpu::VertexAttributeDescriptor] = &[
+        wgpu::VertexAttributeDescriptor { offset: 0, shader_location: 0, format: wgpu::VertexFormat::Float3 },
+        wgpu::VertexAttributeDescriptor { offset: 12, shader_location: 1, format: wgpu::VertexFormat::Float2 },
+    ];
+
+    const INSTANCE_ATTRIBUTES: &[wgpu::VertexAttributeDescriptor] = &[
+        wgpu::VertexAttributeDescriptor { offset: 0, shader_location: 2, format: wgpu::VertexFormat::Float4 },
+        wgpu::VertexAttributeDescriptor { offset: 16, shader_location: 3, format: wgpu::VertexFormat::Float4 },
+        wgpu::VertexAttributeDescriptor { offset: 32, shader_location: 4, format: wgpu::VertexFormat::Float4 },
+        wgpu::VertexAttributeDescriptor { offset: 48, shader_location: 5, format: wgpu::VertexFormat::Float4 },
+    ];
+
+    fn vertex_buffer(attributes: &[wgpu::VertexAttributeDescriptor]) -> wgpu::VertexBufferDescriptor<'_> {
+        wgpu::VertexBufferDescriptor {
+            stride: 64,
+            step_mode: wgpu::InputStepMode::Vertex,
+            attributes,
+        }
+    }
+
+    #[test]
+    fn inputs_come_from_the_vertex_entry_point() {
+        let reflection = reflect_shader();
+        let inputs = reflection
+            .inputs
+            .iter()
+            .map(|input| (input.name.as_str(), input.location, input.kind, input.components))
+            .collect::<Vec<_>>();
+        // The fragment shader's v_tex_coords is at location 0 too, but it
+        // doesn't come from a vertex buffer
+        assert_eq!(
+            inputs,
+            [("a_position", 0, ScalarKind::Float, 3), ("a_tex_coords", 1, ScalarKind::Float, 2)],
+        );
+    }
+
+    #[test]
+    fn matrices_take_a_location_per_column() {
+        let reflection = reflect(&matrix_input()).unwrap();
+        let locations = reflection
+            .inputs
+            .iter()
+            .map(|input| (input.location, input.kind, input.components, input.first_location))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            locations,
+            [
+                (2, ScalarKind::Float, 4, 2),
+                (3, ScalarKind::Float, 4, 2),
+                (4, ScalarKind::Float, 4, 2),
+                (5, ScalarKind::Float, 4, 2),
+            ],
+        );
+        assert_eq!(
+            reflection.inputs[1].to_string(),
+            "vec4 a_model at location 3 (part of mat4 a_model starting at location 2)",
+        );
+    }
+
+    #[test]
+    fn bindings_get_their_set_and_type() {
+        let reflection = reflect_shader();
+        let bindings = reflection
+            .bindings
+            .iter()
+            .map(|binding| (binding.name.as_str(), binding.set, binding.binding, binding.ty))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            bindings,
+            [
+                (
+                    "t_diffuse",
+                    0,
+                    0,
+                    wgpu::BindingType::SampledTexture {
+                        multisampled: false,
+                        dimension: wgpu::TextureViewDimension::D2,
+                    },
+                ),
+                ("s_diffuse", 0, 1, wgpu::BindingType::Sampler),
+                ("uniforms", 1, 0, wgpu::BindingType::UniformBuffer { dynamic: false }),
+            ],
+        );
+    }
+
+    #[test]
+    fn matching_vertex_buffers_pass() {
+        let reflection = reflect_shader();
+        check_vertex_buffers("test.wgsl", &reflection, &[vertex_buffer(VERTEX_ATTRIBUTES)]).unwrap();
+
+        let reflection = reflect(&matrix_input()).unwrap();
+        check_vertex_buffers("test.vert", &reflection, &[vertex_buffer(INSTANCE_ATTRIBUTES)]).unwrap();
+    }
+
+    #[test]
+    fn mismatched_vertex_buffers_are_reported() {
+        let reflection = reflect_shader();
+        // Location 0 shows up twice and the second one, a vec2, wins. Nothing
+        // provides the texture coordinates.
+        let attributes = [wgpu::VertexAttributeDescriptor {
+            offset: 0,
+            shader_location: 0,
+            format: wgpu::VertexFormat::Float2,
+        }];
+        let vertex_buffers = [vertex_buffer(&VERTEX_ATTRIBUTES[..1]), vertex_buffer(&attributes)];
+
+        let error = check_vertex_buffers("test.wgsl", &reflection, &vertex_buffers).unwrap_err();
+        assert_eq!(error.mismatches.len(), 3, "{}", error);
+        assert!(matches!(
+            error.mismatches[0],
+            VertexInputMismatch::Duplicate { location: 0, buffers: (0, 1) }
+        ));
+        assert!(matches!(
+            &error.mismatches[1],
+            VertexInputMismatch::Incompatible { input, buffer: 1, format: wgpu::VertexFormat::Float2 }
+                if input.location == 0
+        ));
+        assert!(matches!(&error.mismatches[2], VertexInputMismatch::Missing(input) if input.location == 1));
+    }
+
+    #[test]
+    fn malformed_spirv_is_an_error() {
+        // An OpTypeStruct without even a result id
+        assert!(reflect(&module(&[instruction(op::TYPE_STRUCT, &[])])).is_err());
+        // An instruction that says it's longer than the module
+        let mut truncated = module(&[instruction(op::TYPE_VECTOR, &[2, 1, 4])]);
+        truncated.pop();
+        assert!(reflect(&truncated).is_err());
+        assert!(reflect(&[MAGIC_NUMBER, 0x0001_0000]).is_err());
+    }
+}
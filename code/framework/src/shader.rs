@@ -0,0 +1,419 @@
+use std::fmt;
+use std::io::{self, Cursor};
+use std::path::{Path, PathBuf};
+
+use crate::preprocessor::{self, Defines, PreprocessError};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ShaderStage {
+    Vertex,
+    Fragment,
+    Compute,
+}
+
+impl ShaderStage {
+    // Guesses the stage from the file extension, the same way glslangValidator does
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "vert" => Some(ShaderStage::Vertex),
+            "frag" => Some(ShaderStage::Fragment),
+            "comp" => Some(ShaderStage::Compute),
+            _ => None,
+        }
+    }
+
+    fn shader_type(self) -> glsl_to_spirv::ShaderType {
+        match self {
+            ShaderStage::Vertex => glsl_to_spirv::ShaderType::Vertex,
+            ShaderStage::Fragment => glsl_to_spirv::ShaderType::Fragment,
+            ShaderStage::Compute => glsl_to_spirv::ShaderType::Compute,
+        }
+    }
+
+    fn naga_stage(self) -> naga::ShaderStage {
+        match self {
+            ShaderStage::Vertex => naga::ShaderStage::Vertex,
+            ShaderStage::Fragment => naga::ShaderStage::Fragment,
+            ShaderStage::Compute => naga::ShaderStage::Compute,
+        }
+    }
+}
+
+impl fmt::Display for ShaderStage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShaderStage::Vertex => write!(f, "vertex"),
+            ShaderStage::Fragment => write!(f, "fragment"),
+            ShaderStage::Compute => write!(f, "compute"),
+        }
+    }
+}
+
+// A shader's source code, in either of the languages we can compile
+#[derive(Debug, Copy, Clone)]
+pub enum ShaderSource<'a> {
+    Glsl(&'a str),
+    Wgsl(&'a str),
+}
+
+// One message from the compiler. glslang doesn't always know where an error
+// is, so the line and column are optional.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    // Set when the message is about a file the shader included
+    pub file: Option<PathBuf>,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub message: String,
+    // The line of the shader the message points at
+    pub source_line: Option<String>,
+}
+
+impl Diagnostic {
+    // A message that isn't about any line in particular
+    fn new(message: String) -> Self {
+        Self {
+            file: None,
+            line: None,
+            column: None,
+            message,
+            source_line: None,
+        }
+    }
+
+    // glslang prints messages like "ERROR: /tmp/.tmpXYZ/0.vert:12: 'foo' : undeclared identifier".
+    // The file is a temporary one, so we throw it away and keep the line number.
+    fn parse(line: &str, src: &str) -> Option<Self> {
+        let rest = line.strip_prefix("ERROR: ")?;
+        let parts = rest.split(':').collect::<Vec<_>>();
+        let is_number = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_digit());
+
+        // Skip the file name, which on windows can have a colon in it too
+        let line_index = parts.iter().skip(1).position(|part| is_number(part)).map(|i| i + 1);
+        let (line_number, column, message) = match line_index {
+            Some(i) => {
+                let line_number = parts[i].parse().ok();
+                let (column, message_start) = match parts.get(i + 1) {
+                    Some(part) if is_number(part) => (part.parse().ok(), i + 2),
+                    _ => (None, i + 1),
+                };
+                (line_number, column, parts[message_start..].join(":"))
+            }
+            None => (None, None, rest.to_string()),
+        };
+
+        let source_line = line_number
+            .and_then(|n: usize| src.lines().nth(n.checked_sub(1)?))
+            .map(|s| s.to_string());
+
+        Some(Self {
+            file: None,
+            line: line_number,
+            column,
+            message: message.trim().to_string(),
+            source_line,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum ShaderErrorKind {
+    Io(io::Error),
+    Preprocess(PreprocessError),
+    Compile(Vec<Diagnostic>),
+    // The compiler ran, but what it gave us isn't spirv wgpu can read
+    InvalidSpirv(io::Error),
+    // A wgsl shader without a `main` function for the stage it's used as
+    MissingEntryPoint,
+    // Asked for a set of defines build.rs didn't compile the shader with
+    MissingVariant(Defines),
+}
+
+#[derive(Debug)]
+pub struct ShaderError {
+    pub name: String,
+    // A wgsl file can hold every stage, so we don't always know which one
+    // failed
+    pub stage: Option<ShaderStage>,
+    pub kind: ShaderErrorKind,
+}
+
+impl ShaderError {
+    fn shader(&self) -> String {
+        match self.stage {
+            Some(stage) => format!("{} shader {}", stage, self.name),
+            None => format!("shader {}", self.name),
+        }
+    }
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            ShaderErrorKind::Io(e) => write!(f, "couldn't read {}: {}", self.shader(), e),
+            ShaderErrorKind::Preprocess(e) => write!(f, "{}: {}", self.shader(), e),
+            ShaderErrorKind::InvalidSpirv(e) => write!(f, "{} produced invalid spirv: {}", self.shader(), e),
+            ShaderErrorKind::MissingEntryPoint => {
+                let stage = self.stage.map(|stage| stage.to_string()).unwrap_or_default();
+                write!(f, "{} doesn't have a [[stage({})]] fn main()", self.shader(), stage)
+            }
+            ShaderErrorKind::MissingVariant(defines) if defines.is_empty() => {
+                write!(f, "{} wasn't compiled without defines, add it to build.rs", self.shader())
+            }
+            ShaderErrorKind::MissingVariant(defines) => write!(
+                f,
+                "{} wasn't compiled with {} defined, add that variant to build.rs",
+                self.shader(),
+                defines,
+            ),
+            ShaderErrorKind::Compile(diagnostics) => {
+                write!(f, "{} failed to compile", self.shader())?;
+                for diagnostic in diagnostics {
+                    match &diagnostic.file {
+                        Some(file) => write!(f, "\n{}", file.display())?,
+                        None => write!(f, "\n{}", self.name)?,
+                    }
+                    if let Some(line) = diagnostic.line {
+                        write!(f, ":{}", line)?;
+                    }
+                    if let Some(column) = diagnostic.column {
+                        write!(f, ":{}", column)?;
+                    }
+                    write!(f, ": {}", diagnostic.message)?;
+
+                    if let (Some(line), Some(source_line)) = (diagnostic.line, &diagnostic.source_line) {
+                        let gutter = line.to_string();
+                        write!(f, "\n{} | {}", gutter, source_line)?;
+                        if let Some(column) = diagnostic.column {
+                            let padding = " ".repeat(gutter.len() + column.saturating_sub(1));
+                            write!(f, "\n{} | ^", padding)?;
+                        }
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ShaderError {}
+
+// Compiles glsl into spirv words that wgpu can turn into a shader module.
+// `name` is only used to make error messages readable.
+pub fn compile_glsl(name: &str, src: &str, stage: ShaderStage) -> Result<Vec<u32>, ShaderError> {
+    let error = |kind| ShaderError {
+        name: name.to_string(),
+        stage: Some(stage),
+        kind,
+    };
+
+    let spirv = glsl_to_spirv::compile(src, stage.shader_type()).map_err(|output| {
+        let mut diagnostics = output
+            .lines()
+            .filter_map(|line| Diagnostic::parse(line, src))
+            // glslang ends with a summary like "2 compilation errors.  No code generated."
+            .filter(|diagnostic| diagnostic.line.is_some())
+            .collect::<Vec<_>>();
+        if diagnostics.is_empty() {
+            diagnostics.push(Diagnostic::new(output.trim().to_string()));
+        }
+        error(ShaderErrorKind::Compile(diagnostics))
+    })?;
+
+    wgpu::read_spirv(spirv).map_err(|e| error(ShaderErrorKind::InvalidSpirv(e)))
+}
+
+// Compiles wgsl into spirv with naga. Unlike glsl, one wgsl file can have an
+// entry point for every stage. If `stage` is given, the shader has to have a
+// `main` function for it, since that's the entry point our pipelines use.
+pub fn compile_wgsl(name: &str, src: &str, stage: Option<ShaderStage>) -> Result<Vec<u32>, ShaderError> {
+    let error = |kind| ShaderError {
+        name: name.to_string(),
+        stage,
+        kind,
+    };
+
+    let module = naga::front::wgsl::parse_str(src).map_err(|e| {
+        // naga counts lines from 1 and uses 0 when it doesn't know where the
+        // error is. `pos` is how many characters of the line come before where
+        // it stopped, which is just past the token it couldn't parse, so it
+        // counts columns from 0.
+        let line = Some(e.line).filter(|&line| line > 0);
+        error(ShaderErrorKind::Compile(vec![Diagnostic {
+            file: None,
+            line,
+            column: line.map(|_| e.pos + 1),
+            message: e.error.to_string(),
+            source_line: line.and_then(|n| src.lines().nth(n - 1)).map(|s| s.to_string()),
+        }]))
+    })?;
+
+    naga::proc::Validator::new()
+        .validate(&module)
+        .map_err(|e| error(ShaderErrorKind::Compile(vec![Diagnostic::new(e.to_string())])))?;
+
+    if let Some(stage) = stage {
+        if !module.entry_points.contains_key(&(stage.naga_stage(), "main".to_string())) {
+            return Err(error(ShaderErrorKind::MissingEntryPoint));
+        }
+    }
+
+    // Debug info keeps the variable names, which the reflection errors use
+    let capabilities = std::iter::once(naga::back::spv::Capability::Shader).collect();
+    naga::back::spv::write_vec(&module, naga::back::spv::WriterFlags::DEBUG, capabilities)
+        .map_err(|e| error(ShaderErrorKind::Compile(vec![Diagnostic::new(e.to_string())])))
+}
+
+pub fn compile(name: &str, source: ShaderSource, stage: ShaderStage) -> Result<Vec<u32>, ShaderError> {
+    match source {
+        ShaderSource::Glsl(src) => compile_glsl(name, src, stage),
+        ShaderSource::Wgsl(src) => compile_wgsl(name, src, Some(stage)),
+    }
+}
+
+// Same as `compile_glsl`, but reads the source from disk instead of from the
+// binary. This also resolves `#include`s, and errors in included files point
+// at the right file and line.
+pub fn load_glsl(path: &Path, stage: ShaderStage) -> Result<Vec<u32>, ShaderError> {
+    load(path, Some(stage), &Defines::new(), |name, src| compile_glsl(name, src, stage))
+}
+
+// The wgsl version of `load_glsl`
+pub fn load_wgsl(path: &Path, stage: Option<ShaderStage>) -> Result<Vec<u32>, ShaderError> {
+    load_wgsl_variant(path, stage, &Defines::new())
+}
+
+// Same as `load_wgsl`, with `defines` deciding which `#ifdef`s are kept
+pub fn load_wgsl_variant(path: &Path, stage: Option<ShaderStage>, defines: &Defines) -> Result<Vec<u32>, ShaderError> {
+    load(path, stage, defines, |name, src| compile_wgsl(name, src, stage))
+}
+
+// Loads a shader in whichever language its extension says it's written in, so
+// ".wgsl" files go through naga and ".vert"/".frag"/".comp" files through glslang
+pub fn load_shader(path: &Path, stage: ShaderStage) -> Result<Vec<u32>, ShaderError> {
+    load_shader_variant(path, stage, &Defines::new())
+}
+
+// Same as `load_shader`, with `defines` deciding which `#ifdef`s are kept
+pub fn load_shader_variant(path: &Path, stage: ShaderStage, defines: &Defines) -> Result<Vec<u32>, ShaderError> {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("wgsl") => load_wgsl_variant(path, Some(stage), defines),
+        _ => load(path, Some(stage), defines, |name, src| compile_glsl(name, src, stage)),
+    }
+}
+
+fn load(
+    path: &Path,
+    stage: Option<ShaderStage>,
+    defines: &Defines,
+    compile: impl FnOnce(&str, &str) -> Result<Vec<u32>, ShaderError>,
+) -> Result<Vec<u32>, ShaderError> {
+    // The defines go in the name, so errors say which variant broke
+    let name = if defines.is_empty() {
+        path.display().to_string()
+    } else {
+        format!("{} ({})", path.display(), defines)
+    };
+    let preprocessed = preprocessor::preprocess_with_defines(path, defines).map_err(|e| ShaderError {
+        name: name.clone(),
+        stage,
+        kind: match e {
+            PreprocessError::Io { error, included_from: None, .. } => ShaderErrorKind::Io(error),
+            e => ShaderErrorKind::Preprocess(e),
+        },
+    })?;
+
+    compile(&name, &preprocessed.source).map_err(|mut e| {
+        if let ShaderErrorKind::Compile(diagnostics) = &mut e.kind {
+            for diagnostic in diagnostics {
+                if let Some(location) = diagnostic.line.and_then(|line| preprocessed.location(line)) {
+                    diagnostic.line = Some(location.line);
+                    if location.file != path || !defines.is_empty() {
+                        diagnostic.file = Some(location.file.clone());
+                    }
+                }
+            }
+        }
+        e
+    })
+}
+
+pub fn create_shader_module(
+    device: &wgpu::Device,
+    name: &str,
+    source: ShaderSource,
+    stage: ShaderStage,
+) -> Result<wgpu::ShaderModule, ShaderError> {
+    let data = compile(name, source, stage)?;
+    Ok(device.create_shader_module(&data))
+}
+
+// Turns spirv bytes, such as the ones from `include_spirv!`, into words
+pub fn read_spirv_bytes(name: &str, stage: ShaderStage, spirv: &[u8]) -> Result<Vec<u32>, ShaderError> {
+    wgpu::read_spirv(Cursor::new(spirv)).map_err(|e| ShaderError {
+        name: name.to_string(),
+        stage: Some(stage),
+        kind: ShaderErrorKind::InvalidSpirv(e),
+    })
+}
+
+// Takes spirv that was compiled ahead of time, usually by `build::compile_shaders`
+pub fn create_spirv_module(
+    device: &wgpu::Device,
+    name: &str,
+    stage: ShaderStage,
+    spirv: &[u8],
+) -> Result<wgpu::ShaderModule, ShaderError> {
+    let data = read_spirv_bytes(name, stage, spirv)?;
+    Ok(device.create_shader_module(&data))
+}
+
+// Embeds the spirv `build::compile_shaders` made for a shader. The path is
+// relative to the crate root, like "src/shader.vert". Variants made by
+// `build::compile_shader_variants` are picked by listing their defines after
+// the path, in the same order as in build.rs:
+// `include_spirv!("src/shader.vert", "USE_VERTEX_COLOR")`.
+#[macro_export]
+macro_rules! include_spirv {
+    ($path:literal $(, $define:literal)* $(,)?) => {
+        include_bytes!(concat!(env!("OUT_DIR"), "/", $path, $(".", $define,)* ".spv"))
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diagnostics(e: ShaderError) -> Vec<Diagnostic> {
+        match e.kind {
+            ShaderErrorKind::Compile(diagnostics) => diagnostics,
+            kind => panic!("expected a compile error, got {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn wgsl_errors_count_columns_from_one() {
+        let src = "[[location(0)]] var<out> f_color: vec4<f32>;\n\n[[stage(fragment)]]\nfn main() {\n    f_color = ;\n}\n";
+        let diagnostics = diagnostics(compile_wgsl("test.wgsl", src, None).unwrap_err());
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].line, Some(5));
+        // naga stops right after the `;` it didn't expect
+        assert_eq!(diagnostics[0].column, Some(16));
+        assert_eq!(diagnostics[0].source_line.as_deref(), Some("    f_color = ;"));
+    }
+
+    // The same thing tutorial4's build script does for its wgsl example
+    #[test]
+    fn load_shader_compiles_wgsl_for_each_stage() {
+        let path = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/../beginner/tutorial4-buffer/examples/shader.wgsl"));
+        for &stage in &[ShaderStage::Vertex, ShaderStage::Fragment] {
+            if let Err(e) = load_shader(path, stage) {
+                panic!("{}", e);
+            }
+        }
+        match load_shader(path, ShaderStage::Compute) {
+            Err(ShaderError { kind: ShaderErrorKind::MissingEntryPoint, .. }) => {}
+            result => panic!("expected a missing entry point, got {:?}", result.map(|_| ())),
+        }
+    }
+}
@@ -0,0 +1,273 @@
+// Generates meshes for simple shapes, so we don't have to type out vertices
+// by hand. Everything is centered on the origin and wound counter clockwise,
+// which is what `FrontFace::Ccw` with `CullMode::Back` expects.
+//
+// The flat shapes lie in the xy plane and face -z, like the pentagon in the
+// tutorials, so the tutorial cameras (which sit at a negative z) see their
+// front. The exception is `grid`, which is a floor in the xz plane facing up.
+// The solid shapes face outwards, and have y as up.
+use std::f32::consts::PI;
+
+use crate::mesh::{MeshData, Vertex};
+
+fn vertex(position: [f32; 3], tex_coords: [f32; 2]) -> Vertex {
+    Vertex { position, tex_coords }
+}
+
+// Two triangles for the quad between four vertices, given as they'd appear
+// on screen when looking at the front of it
+fn push_quad(indices: &mut Vec<u32>, top_left: u32, top_right: u32, bottom_left: u32, bottom_right: u32) {
+    indices.extend_from_slice(&[top_left, bottom_left, bottom_right, top_left, bottom_right, top_right]);
+}
+
+// Texture coordinates for a point on a flat shape facing -z. Seen from the
+// front +x is on the left, so u runs the other way.
+fn flat_tex_coords(x: f32, y: f32, width: f32, height: f32) -> [f32; 2] {
+    [0.5 - x / width, 0.5 - y / height]
+}
+
+// A polygon with `sides` corners, with the first one pointing straight up.
+// It's a fan of triangles from the first corner, so there's no center vertex.
+pub fn regular_polygon(sides: u32, radius: f32) -> MeshData {
+    assert!(sides >= 3, "a polygon needs at least 3 sides");
+
+    let vertices = (0..sides)
+        .map(|i| {
+            let angle = PI / 2.0 + 2.0 * PI * i as f32 / sides as f32;
+            let (x, y) = (radius * angle.cos(), radius * angle.sin());
+            vertex([x, y, 0.0], flat_tex_coords(x, y, 2.0 * radius, 2.0 * radius))
+        })
+        .collect();
+
+    // The corners go counter clockwise looking down -z, so seen from the
+    // front they go clockwise and each triangle is flipped
+    let indices = (1..sides - 1).flat_map(|i| vec![0, i + 1, i]).collect();
+
+    MeshData { vertices, indices }
+}
+
+// A fan around a center vertex, which keeps the triangles from getting long
+// and thin when there are lots of segments
+pub fn circle(radius: f32, segments: u32) -> MeshData {
+    assert!(segments >= 3, "a circle needs at least 3 segments");
+
+    let mut vertices = vec![vertex([0.0, 0.0, 0.0], [0.5, 0.5])];
+    vertices.extend((0..segments).map(|i| {
+        let angle = 2.0 * PI * i as f32 / segments as f32;
+        let (x, y) = (radius * angle.cos(), radius * angle.sin());
+        vertex([x, y, 0.0], flat_tex_coords(x, y, 2.0 * radius, 2.0 * radius))
+    }));
+
+    let indices = (0..segments)
+        .flat_map(|i| vec![0, (i + 1) % segments + 1, i + 1])
+        .collect();
+
+    MeshData { vertices, indices }
+}
+
+pub fn quad(width: f32, height: f32) -> MeshData {
+    let (x, y) = (width / 2.0, height / 2.0);
+    // Top left, top right, bottom left and bottom right, seen from the front
+    let vertices = vec![
+        vertex([x, y, 0.0], [0.0, 0.0]),
+        vertex([-x, y, 0.0], [1.0, 0.0]),
+        vertex([x, -y, 0.0], [0.0, 1.0]),
+        vertex([-x, -y, 0.0], [1.0, 1.0]),
+    ];
+
+    let mut indices = Vec::new();
+    push_quad(&mut indices, 0, 1, 2, 3);
+
+    MeshData { vertices, indices }
+}
+
+// A floor split into `columns` by `rows` squares, facing +y. The texture is
+// stretched over the whole thing.
+pub fn grid(width: f32, depth: f32, columns: u32, rows: u32) -> MeshData {
+    assert!(columns > 0 && rows > 0, "a grid needs at least one column and row");
+
+    let mut vertices = Vec::new();
+    for row in 0..=rows {
+        for column in 0..=columns {
+            let u = column as f32 / columns as f32;
+            let v = row as f32 / rows as f32;
+            vertices.push(vertex([(u - 0.5) * width, 0.0, (v - 0.5) * depth], [u, v]));
+        }
+    }
+
+    // Looking down at the grid, +x is to the right and +z is down
+    let mut indices = Vec::new();
+    let stride = columns + 1;
+    for row in 0..rows {
+        for column in 0..columns {
+            let top_left = row * stride + column;
+            let bottom_left = top_left + stride;
+            push_quad(&mut indices, top_left, top_left + 1, bottom_left, bottom_left + 1);
+        }
+    }
+
+    MeshData { vertices, indices }
+}
+
+// Each face gets its own four vertices, so every face shows the whole texture
+pub fn cube(size: f32) -> MeshData {
+    // The direction each face points, and which way is up on it
+    let faces: [([f32; 3], [f32; 3]); 6] = [
+        ([1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+        ([-1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+        ([0.0, 1.0, 0.0], [0.0, 0.0, -1.0]),
+        ([0.0, -1.0, 0.0], [0.0, 0.0, 1.0]),
+        ([0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),
+        ([0.0, 0.0, -1.0], [0.0, 1.0, 0.0]),
+    ];
+
+    let half = size / 2.0;
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    for &(normal, up) in &faces {
+        // Looking at the face from outside, right is the view direction
+        // (-normal) crossed with up
+        let right = [
+            up[1] * normal[2] - up[2] * normal[1],
+            up[2] * normal[0] - up[0] * normal[2],
+            up[0] * normal[1] - up[1] * normal[0],
+        ];
+
+        let first = vertices.len() as u32;
+        for &(u, v) in &[(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)] {
+            let (x, y) = (u * 2.0 - 1.0, 1.0 - v * 2.0);
+            let position = [
+                half * (normal[0] + right[0] * x + up[0] * y),
+                half * (normal[1] + right[1] * x + up[1] * y),
+                half * (normal[2] + right[2] * x + up[2] * y),
+            ];
+            vertices.push(vertex(position, [u, v]));
+        }
+        push_quad(&mut indices, first, first + 1, first + 2, first + 3);
+    }
+
+    MeshData { vertices, indices }
+}
+
+// A point on a ring around the y axis. As `angle` goes up the point moves to
+// the right, seen from outside with y up, just like u does on a texture.
+fn ring(radius: f32, angle: f32) -> (f32, f32) {
+    (radius * angle.cos(), -radius * angle.sin())
+}
+
+// Wraps a grid of `(columns + 1) * (rows + 1)` vertices around a shape. The
+// extra column is where the texture's seam is. `skip` gets the row and
+// whether it's a quad's upper triangle, to leave out ones with no area.
+fn push_grid(indices: &mut Vec<u32>, columns: u32, rows: u32, skip: impl Fn(u32, bool) -> bool) {
+    let stride = columns + 1;
+    for row in 0..rows {
+        for column in 0..columns {
+            let top_left = row * stride + column;
+            let (top_right, bottom_left, bottom_right) = (top_left + 1, top_left + stride, top_left + stride + 1);
+            if !skip(row, false) {
+                indices.extend_from_slice(&[top_left, bottom_left, bottom_right]);
+            }
+            if !skip(row, true) {
+                indices.extend_from_slice(&[top_left, bottom_right, top_right]);
+            }
+        }
+    }
+}
+
+// A sphere made of `sectors` slices around the y axis and `stacks` bands
+// from top to bottom. The texture wraps around it like a world map.
+pub fn uv_sphere(radius: f32, sectors: u32, stacks: u32) -> MeshData {
+    assert!(sectors >= 3 && stacks >= 2, "a sphere needs at least 3 sectors and 2 stacks");
+
+    let mut vertices = Vec::new();
+    for stack in 0..=stacks {
+        let v = stack as f32 / stacks as f32;
+        let polar = PI * v;
+        let (ring_radius, y) = (radius * polar.sin(), radius * polar.cos());
+        for sector in 0..=sectors {
+            let u = sector as f32 / sectors as f32;
+            let (x, z) = ring(ring_radius, 2.0 * PI * u);
+            vertices.push(vertex([x, y, z], [u, v]));
+        }
+    }
+
+    // The top and bottom bands have one corner of each quad squashed into
+    // the pole, so only one of their triangles has any area
+    let mut indices = Vec::new();
+    push_grid(&mut indices, sectors, stacks, |stack, upper| {
+        (upper && stack == 0) || (!upper && stack == stacks - 1)
+    });
+
+    MeshData { vertices, indices }
+}
+
+// A cylinder standing on the y axis, with a cap on each end
+pub fn cylinder(radius: f32, height: f32, segments: u32) -> MeshData {
+    assert!(segments >= 3, "a cylinder needs at least 3 segments");
+
+    let half = height / 2.0;
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+
+    // The side is a grid with one row
+    for &(y, v) in &[(half, 0.0), (-half, 1.0)] {
+        for segment in 0..=segments {
+            let u = segment as f32 / segments as f32;
+            let (x, z) = ring(radius, 2.0 * PI * u);
+            vertices.push(vertex([x, y, z], [u, v]));
+        }
+    }
+    push_grid(&mut indices, segments, 1, |_, _| false);
+
+    // The caps are circles with the texture stamped on them
+    for &(y, facing_up) in &[(half, true), (-half, false)] {
+        let center = vertices.len() as u32;
+        vertices.push(vertex([0.0, y, 0.0], [0.5, 0.5]));
+        for segment in 0..segments {
+            let (x, z) = ring(radius, 2.0 * PI * segment as f32 / segments as f32);
+            vertices.push(vertex([x, y, z], [0.5 + x / (2.0 * radius), 0.5 + z / (2.0 * radius)]));
+        }
+        for segment in 0..segments {
+            let a = center + 1 + segment;
+            let b = center + 1 + (segment + 1) % segments;
+            if facing_up {
+                indices.extend_from_slice(&[center, a, b]);
+            } else {
+                indices.extend_from_slice(&[center, b, a]);
+            }
+        }
+    }
+
+    MeshData { vertices, indices }
+}
+
+// A donut lying flat in the xz plane. `major_radius` is from the center to
+// the middle of the tube, and `minor_radius` is how thick the tube is.
+pub fn torus(major_radius: f32, minor_radius: f32, major_segments: u32, minor_segments: u32) -> MeshData {
+    assert!(
+        major_segments >= 3 && minor_segments >= 3,
+        "a torus needs at least 3 segments each way",
+    );
+
+    let mut vertices = Vec::new();
+    for minor in 0..=minor_segments {
+        // Start on the outside of the tube and head down, so v goes down the
+        // outside like it does on a sphere
+        let v = minor as f32 / minor_segments as f32;
+        let tube_angle = 2.0 * PI * v;
+        let (distance, y) = (
+            major_radius + minor_radius * tube_angle.cos(),
+            -minor_radius * tube_angle.sin(),
+        );
+        for major in 0..=major_segments {
+            let u = major as f32 / major_segments as f32;
+            let (x, z) = ring(distance, 2.0 * PI * u);
+            vertices.push(vertex([x, y, z], [u, v]));
+        }
+    }
+
+    let mut indices = Vec::new();
+    push_grid(&mut indices, major_segments, minor_segments, |_, _| false);
+
+    MeshData { vertices, indices }
+}
@@ -0,0 +1,426 @@
+// Uploads data to the gpu through a handful of big staging buffers that get
+// reused from frame to frame, instead of making a new `create_buffer_mapped`
+// buffer every time something changes.
+//
+// Each frame goes like this:
+//
+// 1. `write` as much as you like. Every write goes straight into a slice of a
+//    staging buffer, and a copy from there into the real buffer is put in the
+//    encoder.
+// 2. `finish` before submitting the encoder, which closes the staging buffers
+//    the frame wrote to.
+// 3. `recall` after submitting. Once the gpu is done with the frame, its
+//    staging buffers can be written to again.
+//
+// None of this waits for the gpu. Staging buffers it might still be reading
+// are left alone, and if none of the others have room a new one gets made.
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::pod::{self, Pod};
+
+// Copies between buffers have to start on a multiple of 4 bytes
+const ALIGNMENT: wgpu::BufferAddress = 4;
+
+fn align(offset: wgpu::BufferAddress) -> wgpu::BufferAddress {
+    offset + (ALIGNMENT - offset % ALIGNMENT) % ALIGNMENT
+}
+
+enum ChunkState {
+    // The gpu isn't using it, so it can take writes
+    Free,
+    // Written, and waiting for the encoder that copies out of it to be
+    // submitted
+    Closed,
+    // Submitted. The flag gets set when the gpu is done with it, which we
+    // find out by asking to map it.
+    InFlight(Arc<AtomicBool>),
+}
+
+// The bookkeeping for one staging buffer. It's kept apart from the buffer so
+// which chunk a write goes into can be worked out without a gpu.
+struct Chunk {
+    size: wgpu::BufferAddress,
+    // Where the next write goes
+    used: wgpu::BufferAddress,
+    state: ChunkState,
+}
+
+impl Chunk {
+    // Where a write of `size` bytes would go, if this chunk can take it
+    fn offset_for(&self, size: wgpu::BufferAddress) -> Option<wgpu::BufferAddress> {
+        match self.state {
+            ChunkState::Free => Some(align(self.used)).filter(|&offset| offset + size <= self.size),
+            _ => None,
+        }
+    }
+
+    // Maps just the part of `buffer` being written and copies `bytes` in.
+    // wgpu maps buffers no submission is using as soon as we poll, which is
+    // every Free chunk, so this doesn't wait. If the map fails anyway, the
+    // chunk is put aside until it can be mapped and we get false.
+    fn try_write(
+        &mut self,
+        buffer: &wgpu::Buffer,
+        device: &wgpu::Device,
+        offset: wgpu::BufferAddress,
+        bytes: &[u8],
+    ) -> bool {
+        // The buffer is only mapped inside the callback, so the data has to
+        // go in there with it
+        let data = bytes.to_vec();
+        let done = Arc::new(AtomicBool::new(false));
+        let written = Arc::new(AtomicBool::new(false));
+        let (callback_done, callback_written) = (done.clone(), written.clone());
+        buffer.map_write_async(
+            offset,
+            align(data.len() as wgpu::BufferAddress),
+            move |result: wgpu::BufferMapAsyncResult<&mut [u8]>| {
+                if let Ok(mapping) = result {
+                    mapping.data[..data.len()].copy_from_slice(&data);
+                    callback_written.store(true, Ordering::SeqCst);
+                }
+                callback_done.store(true, Ordering::SeqCst);
+            },
+        );
+
+        device.poll(false);
+        if written.load(Ordering::SeqCst) {
+            buffer.unmap();
+            return true;
+        }
+
+        self.state = if done.load(Ordering::SeqCst) {
+            // Nothing is pending, so `recall` can fence it like any other
+            ChunkState::Closed
+        } else {
+            // Still waiting on the gpu, so the map we asked for is the fence.
+            // wgpu won't submit a buffer with a map pending, but only a chunk
+            // the gpu was already using can get here, and those aren't
+            // written to until it's done.
+            debug_assert_eq!(self.used, 0);
+            ChunkState::InFlight(done)
+        };
+        false
+    }
+}
+
+// How much of the staging memory a frame used
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct StagingUsage {
+    pub writes: u32,
+    // What was passed to `write`
+    pub bytes_written: wgpu::BufferAddress,
+    // `bytes_written`, plus the padding to keep every write aligned
+    pub bytes_used: wgpu::BufferAddress,
+    pub chunks_used: usize,
+    // Every staging buffer the ring has made so far, in use or not
+    pub chunks: usize,
+    pub capacity: wgpu::BufferAddress,
+}
+
+impl fmt::Display for StagingUsage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} writes, {} bytes ({} with padding) in {} of {} staging buffers ({} bytes)",
+            self.writes, self.bytes_written, self.bytes_used, self.chunks_used, self.chunks, self.capacity,
+        )
+    }
+}
+
+// Which chunk each write goes into, and how much of the staging memory that
+// uses. `StagingRing` keeps a buffer for each of these chunks.
+struct Chunks {
+    chunk_size: wgpu::BufferAddress,
+    chunks: Vec<Chunk>,
+    // Adds up the frame being written, until `finish`
+    frame_usage: StagingUsage,
+}
+
+impl Chunks {
+    fn new(chunk_size: wgpu::BufferAddress) -> Self {
+        Self {
+            chunk_size,
+            chunks: Vec::new(),
+            frame_usage: StagingUsage::default(),
+        }
+    }
+
+    // The first chunk with room for `size` bytes, and where they'd go in it
+    fn find(&self, size: wgpu::BufferAddress) -> Option<(usize, wgpu::BufferAddress)> {
+        self.chunks
+            .iter()
+            .enumerate()
+            .find_map(|(index, chunk)| Some((index, chunk.offset_for(size)?)))
+    }
+
+    // Adds a chunk with room for at least `size` bytes
+    fn push(&mut self, size: wgpu::BufferAddress) -> usize {
+        self.chunks.push(Chunk {
+            size: align(size.max(self.chunk_size)),
+            used: 0,
+            state: ChunkState::Free,
+        });
+        self.chunks.len() - 1
+    }
+
+    // Counts `size` bytes written at `offset` in chunk `index`
+    fn record(&mut self, index: usize, offset: wgpu::BufferAddress, size: wgpu::BufferAddress) {
+        let chunk = &mut self.chunks[index];
+        if chunk.used == 0 {
+            self.frame_usage.chunks_used += 1;
+        }
+        self.frame_usage.writes += 1;
+        self.frame_usage.bytes_written += size;
+        self.frame_usage.bytes_used += offset + size - chunk.used;
+        chunk.used = offset + size;
+    }
+
+    // Closes the chunks this frame wrote to, and returns what it used
+    fn finish(&mut self) -> StagingUsage {
+        for chunk in &mut self.chunks {
+            if let ChunkState::Free = chunk.state {
+                if chunk.used > 0 {
+                    chunk.state = ChunkState::Closed;
+                }
+            }
+        }
+
+        self.frame_usage.chunks = self.chunks.len();
+        self.frame_usage.capacity = self.chunks.iter().map(|chunk| chunk.size).sum();
+        std::mem::take(&mut self.frame_usage)
+    }
+
+    // Frees the chunks the gpu is done with, and returns which ones they were
+    // so their buffers can be unmapped
+    fn reclaim(&mut self) -> Vec<usize> {
+        let mut reclaimed = Vec::new();
+        for (index, chunk) in self.chunks.iter_mut().enumerate() {
+            let ready = match &chunk.state {
+                ChunkState::InFlight(ready) => ready.load(Ordering::SeqCst),
+                _ => false,
+            };
+            if ready {
+                chunk.used = 0;
+                chunk.state = ChunkState::Free;
+                reclaimed.push(index);
+            }
+        }
+        reclaimed
+    }
+}
+
+pub struct StagingRing {
+    chunks: Chunks,
+    // One for each of `chunks`
+    buffers: Vec<wgpu::Buffer>,
+    usage: StagingUsage,
+}
+
+impl StagingRing {
+    // `chunk_size` is how big each staging buffer is. Writes bigger than that
+    // get a buffer of their own.
+    pub fn new(chunk_size: wgpu::BufferAddress) -> Self {
+        Self {
+            chunks: Chunks::new(chunk_size),
+            buffers: Vec::new(),
+            usage: StagingUsage::default(),
+        }
+    }
+
+    // Copies `data` into `target` at `target_offset`, once `encoder` is submitted
+    pub fn write<T: Pod>(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::Buffer,
+        target_offset: wgpu::BufferAddress,
+        data: &[T],
+    ) {
+        let bytes = pod::as_bytes(data);
+        let size = bytes.len() as wgpu::BufferAddress;
+        if size == 0 {
+            return;
+        }
+
+        let mut reclaimed = false;
+        let (index, offset) = loop {
+            let (index, offset) = match self.chunks.find(size) {
+                Some(found) => found,
+                // Before making another buffer, see if the gpu has finished
+                // with any of the old ones
+                None if !reclaimed => {
+                    self.reclaim(device);
+                    reclaimed = true;
+                    continue;
+                }
+                None => break (self.create_chunk(device, bytes), 0),
+            };
+            if self.chunks.chunks[index].try_write(&self.buffers[index], device, offset, bytes) {
+                break (index, offset);
+            }
+        };
+
+        self.chunks.record(index, offset, size);
+        encoder.copy_buffer_to_buffer(&self.buffers[index], offset, target, target_offset, size);
+    }
+
+    // New buffers can start out mapped, so `bytes` go in as it's made and
+    // there's nothing to wait for
+    fn create_chunk(&mut self, device: &wgpu::Device, bytes: &[u8]) -> usize {
+        let index = self.chunks.push(bytes.len() as wgpu::BufferAddress);
+        let mapped = device.create_buffer_mapped::<u8>(
+            self.chunks.chunks[index].size as usize,
+            wgpu::BufferUsage::MAP_WRITE | wgpu::BufferUsage::COPY_SRC,
+        );
+        mapped.data[..bytes.len()].copy_from_slice(bytes);
+        self.buffers.push(mapped.finish());
+        index
+    }
+
+    // Closes the staging buffers this frame wrote to. Call it after the last
+    // `write`, and before submitting the encoder they went into.
+    pub fn finish(&mut self) {
+        self.usage = self.chunks.finish();
+    }
+
+    // Call this after submitting the encoder passed to `write`. The staging
+    // buffers it used get handed back out once the gpu is done with them.
+    pub fn recall(&mut self, device: &wgpu::Device) {
+        for (chunk, buffer) in self.chunks.chunks.iter_mut().zip(&self.buffers) {
+            // wgpu only maps a buffer once the gpu has stopped using it, so
+            // the map doubles as a fence
+            if let ChunkState::Closed = chunk.state {
+                let ready = Arc::new(AtomicBool::new(false));
+                let callback_ready = ready.clone();
+                buffer.map_write_async(
+                    0,
+                    chunk.size,
+                    move |_: wgpu::BufferMapAsyncResult<&mut [u8]>| callback_ready.store(true, Ordering::SeqCst),
+                );
+                chunk.state = ChunkState::InFlight(ready);
+            }
+        }
+        self.reclaim(device);
+    }
+
+    // Frees the chunks the gpu is done with, without waiting for the rest
+    fn reclaim(&mut self, device: &wgpu::Device) {
+        device.poll(false);
+        for index in self.chunks.reclaim() {
+            self.buffers[index].unmap();
+        }
+    }
+
+    // What the last frame to `finish` used
+    pub fn usage(&self) -> StagingUsage {
+        self.usage
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // What `StagingRing::write` does, minus the buffers
+    fn write(chunks: &mut Chunks, size: wgpu::BufferAddress) -> (usize, wgpu::BufferAddress) {
+        let found = chunks.find(size).or_else(|| {
+            chunks.reclaim();
+            chunks.find(size)
+        });
+        let (index, offset) = found.unwrap_or_else(|| (chunks.push(size), 0));
+        chunks.record(index, offset, size);
+        (index, offset)
+    }
+
+    // What `StagingRing::recall` does, with fences the test sets by hand
+    // instead of the gpu
+    fn recall(chunks: &mut Chunks) -> Vec<Arc<AtomicBool>> {
+        let mut fences = Vec::new();
+        for chunk in &mut chunks.chunks {
+            if let ChunkState::Closed = chunk.state {
+                let fence = Arc::new(AtomicBool::new(false));
+                chunk.state = ChunkState::InFlight(fence.clone());
+                fences.push(fence);
+            }
+        }
+        chunks.reclaim();
+        fences
+    }
+
+    #[test]
+    fn writes_share_a_chunk_until_it_fills_up() {
+        let mut chunks = Chunks::new(16);
+        assert_eq!(write(&mut chunks, 3), (0, 0));
+        // Copies start on a multiple of 4
+        assert_eq!(write(&mut chunks, 4), (0, 4));
+        assert_eq!(write(&mut chunks, 8), (0, 8));
+        assert_eq!(write(&mut chunks, 1), (1, 0));
+
+        assert_eq!(
+            chunks.finish(),
+            StagingUsage {
+                writes: 4,
+                bytes_written: 16,
+                bytes_used: 17,
+                chunks_used: 2,
+                chunks: 2,
+                capacity: 32,
+            },
+        );
+    }
+
+    #[test]
+    fn big_writes_get_a_chunk_of_their_own() {
+        let mut chunks = Chunks::new(16);
+        assert_eq!(write(&mut chunks, 4), (0, 0));
+        assert_eq!(write(&mut chunks, 40), (1, 0));
+        assert_eq!(write(&mut chunks, 42), (2, 0));
+        // The first chunk still has room for small writes
+        assert_eq!(write(&mut chunks, 4), (0, 4));
+
+        let sizes = chunks.chunks.iter().map(|chunk| chunk.size).collect::<Vec<_>>();
+        assert_eq!(sizes, [16, 40, 44]);
+    }
+
+    #[test]
+    fn chunks_are_recycled_once_the_gpu_is_done() {
+        let mut chunks = Chunks::new(16);
+        assert_eq!(write(&mut chunks, 8), (0, 0));
+        chunks.finish();
+        let fences = recall(&mut chunks);
+
+        // The gpu might still be reading the first chunk, so it's left alone
+        // even though it has room
+        assert_eq!(write(&mut chunks, 8), (1, 0));
+        chunks.finish();
+        recall(&mut chunks);
+
+        fences[0].store(true, Ordering::SeqCst);
+        assert_eq!(write(&mut chunks, 8), (0, 0));
+        assert_eq!(write(&mut chunks, 8), (0, 8));
+        let usage = chunks.finish();
+        assert_eq!((usage.chunks_used, usage.chunks, usage.capacity), (1, 2, 32));
+    }
+
+    #[test]
+    fn the_ring_wraps_around_when_the_gpu_keeps_up() {
+        let mut chunks = Chunks::new(16);
+        let mut fences: Vec<Arc<AtomicBool>> = Vec::new();
+        let mut used = Vec::new();
+        for frame in 0..9 {
+            // The gpu is two frames behind
+            if frame >= 3 {
+                fences[frame - 3].store(true, Ordering::SeqCst);
+            }
+            used.push(write(&mut chunks, 16).0);
+            chunks.finish();
+            fences.extend(recall(&mut chunks));
+        }
+
+        assert_eq!(used, [0, 1, 2, 0, 1, 2, 0, 1, 2]);
+        assert_eq!(chunks.chunks.len(), 3);
+    }
+}
@@ -0,0 +1,154 @@
+// The texture loading from tutorial5, pulled out so models can load their own
+use std::path::Path;
+
+use image::GenericImageView;
+
+pub struct Texture {
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+    pub sampler: wgpu::Sampler,
+}
+
+// The sampler the tutorials use, with a choice of what happens outside 0 to 1
+pub fn sampler_descriptor(address_mode: wgpu::AddressMode) -> wgpu::SamplerDescriptor {
+    wgpu::SamplerDescriptor {
+        address_mode_u: address_mode,
+        address_mode_v: address_mode,
+        address_mode_w: address_mode,
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Nearest,
+        mipmap_filter: wgpu::FilterMode::Nearest,
+        lod_min_clamp: -100.0,
+        lod_max_clamp: 100.0,
+        compare_function: wgpu::CompareFunction::Always,
+    }
+}
+
+impl Texture {
+    pub fn load<P: AsRef<Path>>(
+        device: &wgpu::Device,
+        queue: &mut wgpu::Queue,
+        path: P,
+        sampler: &wgpu::SamplerDescriptor,
+    ) -> Result<Self, image::ImageError> {
+        let image = image::open(path)?;
+        Ok(Self::from_image(device, queue, &image, sampler))
+    }
+
+    pub fn from_bytes(
+        device: &wgpu::Device,
+        queue: &mut wgpu::Queue,
+        bytes: &[u8],
+        sampler: &wgpu::SamplerDescriptor,
+    ) -> Result<Self, image::ImageError> {
+        let image = image::load_from_memory(bytes)?;
+        Ok(Self::from_image(device, queue, &image, sampler))
+    }
+
+    pub fn from_image(
+        device: &wgpu::Device,
+        queue: &mut wgpu::Queue,
+        image: &image::DynamicImage,
+        sampler: &wgpu::SamplerDescriptor,
+    ) -> Self {
+        // Not every image has an alpha channel, so convert instead of using
+        // `as_rgba8` like the tutorials do
+        let rgba = image.to_rgba();
+        let (width, height) = image.dimensions();
+        Self::from_rgba(device, queue, width, height, &rgba, sampler)
+    }
+
+    // `pixels` is 4 bytes per pixel, in sRGB, a row at a time from the top
+    pub fn from_rgba(
+        device: &wgpu::Device,
+        queue: &mut wgpu::Queue,
+        width: u32,
+        height: u32,
+        pixels: &[u8],
+        sampler: &wgpu::SamplerDescriptor,
+    ) -> Self {
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            size,
+            array_layer_count: 1,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+        });
+
+        let buffer = device
+            .create_buffer_mapped(pixels.len(), wgpu::BufferUsage::COPY_SRC)
+            .fill_from_slice(pixels);
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            todo: 0,
+        });
+
+        encoder.copy_buffer_to_texture(
+            wgpu::BufferCopyView {
+                buffer: &buffer,
+                offset: 0,
+                row_pitch: 4 * width,
+                image_height: height,
+            },
+            wgpu::TextureCopyView {
+                texture: &texture,
+                mip_level: 0,
+                array_layer: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            size,
+        );
+
+        queue.submit(&[encoder.finish()]);
+
+        let view = texture.create_default_view();
+        let sampler = device.create_sampler(sampler);
+
+        Self { texture, view, sampler }
+    }
+
+    // The same layout as the `texture_bind_group_layout` in the tutorials:
+    // the texture at binding 0 and its sampler at binding 1
+    pub fn create_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            bindings: &[
+                wgpu::BindGroupLayoutBinding {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::SampledTexture {
+                        multisampled: false,
+                        dimension: wgpu::TextureViewDimension::D2,
+                    },
+                },
+                wgpu::BindGroupLayoutBinding {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler,
+                },
+            ],
+        })
+    }
+
+    pub fn create_bind_group(&self, device: &wgpu::Device, layout: &wgpu::BindGroupLayout) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            bindings: &[
+                wgpu::Binding {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&self.view),
+                },
+                wgpu::Binding {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        })
+    }
+}
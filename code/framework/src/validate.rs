@@ -0,0 +1,390 @@
+// Checks vertex and index data before it goes to the gpu. Most mistakes here
+// don't cause an error, the geometry just doesn't show up, so it's a lot
+// easier to catch them while we still have the data on the cpu.
+use std::fmt;
+
+// Only this many issues get printed, so a mesh with every triangle flipped
+// doesn't flood the terminal
+const MAX_DISPLAYED: usize = 20;
+
+// Triangles thinner than this are treated as lines. It's the squared sine of
+// the smallest angle we'll accept, so it doesn't depend on the mesh's scale.
+const DEGENERATE_EPSILON: f32 = 1e-10;
+
+// Where the triangles are going to be looked at from. The rasterizer decides
+// which side of a triangle we're seeing from the order its corners end up in
+// on screen, so we need to know where the screen is to check the winding.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ViewedFrom {
+    // From far away in this direction, like a shape drawn straight in clip
+    // space, which is seen from -z
+    Direction([f32; 3]),
+    // From outside, for closed meshes around this point. Only works for
+    // shapes where every face can see the point, like cubes and spheres.
+    Outside([f32; 3]),
+}
+
+// The parts of the `RenderPipelineDescriptor` that say how the data is read
+#[derive(Debug, Copy, Clone)]
+pub struct ValidationDescriptor {
+    pub primitive_topology: wgpu::PrimitiveTopology,
+    pub front_face: wgpu::FrontFace,
+    pub cull_mode: wgpu::CullMode,
+    // Leave this out to skip checking the winding
+    pub viewed_from: Option<ViewedFrom>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MeshIssue {
+    // A NaN or infinite position, which can take the whole triangle with it
+    NonFinitePosition { vertex: usize },
+    IndexOutOfRange { index: usize, value: u32 },
+    // The indices don't split evenly into points, lines or triangles, so the
+    // leftovers get dropped
+    IncompletePrimitive { num_indices: usize, per_primitive: usize },
+    // Two corners in the same place, or all three in a line, so there's
+    // nothing to draw
+    Degenerate { triangle: usize },
+    // The side we'll be looking at is the one `cull_mode` throws away
+    Culled { triangle: usize },
+}
+
+impl fmt::Display for MeshIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MeshIssue::NonFinitePosition { vertex } => write!(f, "vertex {} has a NaN or infinite position", vertex),
+            MeshIssue::IndexOutOfRange { index, value } => {
+                write!(f, "index {} is {}, which is past the last vertex", index, value)
+            }
+            MeshIssue::IncompletePrimitive { num_indices, per_primitive } => write!(
+                f,
+                "{} indices isn't a multiple of {}, so the last {} are ignored",
+                num_indices,
+                per_primitive,
+                num_indices % per_primitive,
+            ),
+            MeshIssue::Degenerate { triangle } => write!(f, "triangle {} has no area", triangle),
+            MeshIssue::Culled { triangle } => {
+                write!(f, "triangle {} is wound the wrong way and will be culled", triangle)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ValidationReport {
+    pub num_vertices: usize,
+    pub num_indices: usize,
+    pub issues: Vec<MeshIssue>,
+}
+
+impl ValidationReport {
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} issues in a mesh with {} vertices and {} indices",
+            self.issues.len(),
+            self.num_vertices,
+            self.num_indices,
+        )?;
+        for issue in self.issues.iter().take(MAX_DISPLAYED) {
+            write!(f, "\n  {}", issue)?;
+        }
+        if self.issues.len() > MAX_DISPLAYED {
+            write!(f, "\n  ...and {} more", self.issues.len() - MAX_DISPLAYED)?;
+        }
+        Ok(())
+    }
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+// Works with any vertex type. `position` pulls the position out of a vertex,
+// and the indices can be u16 or u32.
+pub fn validate<V, I, F>(vertices: &[V], position: F, indices: &[I], desc: &ValidationDescriptor) -> ValidationReport
+where
+    I: Copy + Into<u32>,
+    F: Fn(&V) -> [f32; 3],
+{
+    let mut issues = Vec::new();
+
+    let positions = vertices.iter().map(position).collect::<Vec<_>>();
+    for (vertex, p) in positions.iter().enumerate() {
+        if !p.iter().all(|x| x.is_finite()) {
+            issues.push(MeshIssue::NonFinitePosition { vertex });
+        }
+    }
+
+    let indices = indices.iter().map(|&i| i.into()).collect::<Vec<u32>>();
+    for (index, &value) in indices.iter().enumerate() {
+        if value as usize >= positions.len() {
+            issues.push(MeshIssue::IndexOutOfRange { index, value });
+        }
+    }
+
+    let per_primitive = match desc.primitive_topology {
+        wgpu::PrimitiveTopology::LineList => 2,
+        wgpu::PrimitiveTopology::TriangleList => 3,
+        _ => 1,
+    };
+    if indices.len() % per_primitive != 0 {
+        issues.push(MeshIssue::IncompletePrimitive {
+            num_indices: indices.len(),
+            per_primitive,
+        });
+    }
+
+    // Each triangle's corners, in the order the rasterizer sees them. Every
+    // other triangle in a strip is flipped so they all face the same way.
+    let triangles = match desc.primitive_topology {
+        wgpu::PrimitiveTopology::TriangleList => indices.chunks_exact(3).map(|t| [t[0], t[1], t[2]]).collect(),
+        wgpu::PrimitiveTopology::TriangleStrip => indices
+            .windows(3)
+            .enumerate()
+            .map(|(i, t)| if i % 2 == 0 { [t[0], t[1], t[2]] } else { [t[1], t[0], t[2]] })
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    for (triangle, corners) in triangles.iter().enumerate() {
+        // Out of range indices were already reported
+        if corners.iter().any(|&i| i as usize >= positions.len()) {
+            continue;
+        }
+        let [a, b, c] = [
+            positions[corners[0] as usize],
+            positions[corners[1] as usize],
+            positions[corners[2] as usize],
+        ];
+        if ![a, b, c].iter().flatten().all(|x| x.is_finite()) {
+            continue;
+        }
+
+        let ab = sub(b, a);
+        let ac = sub(c, a);
+        // Points out of the side the corners look counter clockwise from
+        let normal = cross(ab, ac);
+        if dot(normal, normal) <= DEGENERATE_EPSILON * dot(ab, ab) * dot(ac, ac) {
+            issues.push(MeshIssue::Degenerate { triangle });
+            continue;
+        }
+
+        let to_viewer = match desc.viewed_from {
+            Some(ViewedFrom::Direction(direction)) => direction,
+            Some(ViewedFrom::Outside(center)) => sub(a, center),
+            None => continue,
+        };
+        let facing = dot(normal, to_viewer);
+        // Seen edge on, so it's not drawn either way
+        if facing == 0.0 {
+            continue;
+        }
+
+        let sees_front = match desc.front_face {
+            wgpu::FrontFace::Ccw => facing > 0.0,
+            wgpu::FrontFace::Cw => facing < 0.0,
+        };
+        let culled = match desc.cull_mode {
+            wgpu::CullMode::None => false,
+            wgpu::CullMode::Front => sees_front,
+            wgpu::CullMode::Back => !sees_front,
+        };
+        if culled {
+            issues.push(MeshIssue::Culled { triangle });
+        }
+    }
+
+    ValidationReport {
+        num_vertices: positions.len(),
+        num_indices: indices.len(),
+        issues,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shapes;
+
+    const TRIANGLES: ValidationDescriptor = ValidationDescriptor {
+        primitive_topology: wgpu::PrimitiveTopology::TriangleList,
+        front_face: wgpu::FrontFace::Ccw,
+        cull_mode: wgpu::CullMode::Back,
+        viewed_from: None,
+    };
+
+    // How the tutorials draw straight into clip space
+    const CLIP_SPACE: ValidationDescriptor = ValidationDescriptor {
+        viewed_from: Some(ViewedFrom::Direction([0.0, 0.0, -1.0])),
+        ..TRIANGLES
+    };
+
+    // tutorial4's pentagon
+    const PENTAGON: &[[f32; 3]] = &[
+        [-0.0868241, -0.49240386, 0.0],
+        [-0.49513406, -0.06958647, 0.0],
+        [-0.21918549, 0.44939706, 0.0],
+        [0.35966998, 0.3473291, 0.0],
+        [0.44147372, -0.2347359, 0.0],
+    ];
+    const PENTAGON_INDICES: &[u16] = &[0, 1, 4, 1, 2, 4, 2, 3, 4];
+
+    fn position(p: &[f32; 3]) -> [f32; 3] {
+        *p
+    }
+
+    fn flip(indices: &[u32]) -> Vec<u32> {
+        indices.chunks_exact(3).flat_map(|t| vec![t[0], t[2], t[1]]).collect()
+    }
+
+    #[test]
+    fn non_finite_positions_are_reported() {
+        let vertices = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [f32::NAN, 0.0, 0.0], [0.0, f32::INFINITY, 0.0]];
+        let report = validate(&vertices, position, &[0u32, 1, 2, 0, 1, 3], &CLIP_SPACE);
+        // The triangles using them can't be checked, so only the vertices
+        // are reported
+        assert_eq!(
+            report.issues,
+            [MeshIssue::NonFinitePosition { vertex: 2 }, MeshIssue::NonFinitePosition { vertex: 3 }],
+        );
+    }
+
+    #[test]
+    fn out_of_range_indices_are_reported() {
+        let report = validate(PENTAGON, position, &[0u32, 1, 4, 1, 2, 5], &CLIP_SPACE);
+        assert_eq!(report.issues, [MeshIssue::IndexOutOfRange { index: 5, value: 5 }]);
+        assert_eq!((report.num_vertices, report.num_indices), (5, 6));
+    }
+
+    #[test]
+    fn incomplete_primitives_are_reported() {
+        let report = validate(PENTAGON, position, &PENTAGON_INDICES[..8], &TRIANGLES);
+        assert_eq!(
+            report.issues,
+            [MeshIssue::IncompletePrimitive { num_indices: 8, per_primitive: 3 }],
+        );
+
+        let lines = ValidationDescriptor {
+            primitive_topology: wgpu::PrimitiveTopology::LineList,
+            ..TRIANGLES
+        };
+        let report = validate(PENTAGON, position, &[0u16, 1, 2], &lines);
+        assert_eq!(
+            report.issues,
+            [MeshIssue::IncompletePrimitive { num_indices: 3, per_primitive: 2 }],
+        );
+
+        // Strips share corners, so any count works
+        let strip = ValidationDescriptor {
+            primitive_topology: wgpu::PrimitiveTopology::TriangleStrip,
+            ..TRIANGLES
+        };
+        assert!(validate(PENTAGON, position, &[0u16, 1, 2, 3], &strip).is_ok());
+    }
+
+    #[test]
+    fn degenerate_triangles_are_reported() {
+        let vertices = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [2.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+        // A repeated corner, three corners in a line, then a real triangle
+        let report = validate(&vertices, position, &[0u32, 1, 1, 0, 1, 2, 0, 3, 1], &CLIP_SPACE);
+        assert_eq!(
+            report.issues,
+            [MeshIssue::Degenerate { triangle: 0 }, MeshIssue::Degenerate { triangle: 1 }],
+        );
+
+        // Tiny triangles are fine, as long as they have some area
+        let tiny = vertices.iter().map(|p| [p[0] * 1e-3, p[1] * 1e-3, p[2]]).collect::<Vec<_>>();
+        assert!(validate(&tiny, position, &[0u32, 3, 1], &CLIP_SPACE).is_ok());
+    }
+
+    // The challenge draws a circle as a fan of triangles around its first
+    // vertex, and it only shows up when they're wound like the pentagon
+    #[test]
+    fn the_tutorial4_challenge_is_checked_against_the_pipeline() {
+        assert!(validate(PENTAGON, position, PENTAGON_INDICES, &CLIP_SPACE).is_ok());
+
+        let num_vertices = 16;
+        let angle = std::f32::consts::PI * 2.0 / num_vertices as f32;
+        let vertices = (0..num_vertices)
+            .map(|i| {
+                let theta = angle * i as f32;
+                [0.5 * theta.cos(), 0.5 * theta.sin(), 0.0]
+            })
+            .collect::<Vec<_>>();
+        let num_triangles = num_vertices - 2;
+
+        let challenge_indices = (1u16..num_triangles + 1).flat_map(|i| vec![i + 1, i, 0]).collect::<Vec<_>>();
+        assert!(validate(&vertices, position, &challenge_indices, &CLIP_SPACE).is_ok());
+
+        // Going around the other way gets every triangle culled
+        let reversed = (1u16..num_triangles + 1).flat_map(|i| vec![0, i, i + 1]).collect::<Vec<_>>();
+        let report = validate(&vertices, position, &reversed, &CLIP_SPACE);
+        let expected = (0..num_triangles as usize).map(|triangle| MeshIssue::Culled { triangle }).collect::<Vec<_>>();
+        assert_eq!(report.issues, expected);
+
+        // Seen from the other side, it's the other way around
+        let from_behind = ValidationDescriptor {
+            viewed_from: Some(ViewedFrom::Direction([0.0, 0.0, 1.0])),
+            ..CLIP_SPACE
+        };
+        assert!(validate(&vertices, position, &reversed, &from_behind).is_ok());
+        assert_eq!(validate(&vertices, position, &challenge_indices, &from_behind).issues, expected);
+    }
+
+    #[test]
+    fn closed_meshes_are_checked_from_outside() {
+        let cube = shapes::cube(1.0);
+        let outside = ValidationDescriptor {
+            viewed_from: Some(ViewedFrom::Outside([0.0, 0.0, 0.0])),
+            ..TRIANGLES
+        };
+        assert!(cube.validate(&outside).is_ok());
+
+        let inside_out = flip(&cube.indices);
+        let report = validate(&cube.vertices, |v| v.position, &inside_out, &outside);
+        assert_eq!(report.issues.len(), cube.indices.len() / 3);
+        assert!(report.issues.iter().all(|issue| matches!(issue, MeshIssue::Culled { .. })));
+
+        // The pipeline decides which winding is the right one
+        let clockwise = ValidationDescriptor {
+            front_face: wgpu::FrontFace::Cw,
+            ..outside
+        };
+        assert!(validate(&cube.vertices, |v| v.position, &inside_out, &clockwise).is_ok());
+        let no_culling = ValidationDescriptor {
+            cull_mode: wgpu::CullMode::None,
+            ..outside
+        };
+        assert!(validate(&cube.vertices, |v| v.position, &inside_out, &no_culling).is_ok());
+    }
+
+    #[test]
+    fn reports_only_show_the_first_few_issues() {
+        let indices = flip(&shapes::circle(0.5, 32).indices);
+        let report = validate(&shapes::circle(0.5, 32).vertices, |v| v.position, &indices, &CLIP_SPACE);
+        let text = report.to_string();
+        assert!(text.starts_with("32 issues in a mesh with 33 vertices and 96 indices"), "{}", text);
+        assert_eq!(text.lines().count(), 1 + MAX_DISPLAYED + 1);
+        assert!(text.ends_with("...and 12 more"), "{}", text);
+    }
+}
@@ -0,0 +1,166 @@
+// Lets one shader file turn into several shaders, depending on which names
+// are `#define`d when it's compiled
+use std::collections::HashMap;
+use std::hash::Hash;
+#[cfg(feature = "hot-reload")]
+use std::path::PathBuf;
+
+use crate::preprocessor::Defines;
+use crate::shader::{self, ShaderError, ShaderErrorKind, ShaderStage};
+
+pub struct Variant {
+    // Kept around so the pipeline can reflect on it
+    pub spirv: Vec<u32>,
+    pub module: wgpu::ShaderModule,
+}
+
+// Where the spirv for each variant comes from
+enum Source {
+    // Compiled ahead of time by `build::compile_shader_variants`, and
+    // embedded with `include_spirv!`
+    Embedded(HashMap<Defines, &'static [u8]>),
+    // A shader that doesn't `#ifdef` anything, so every variant is the same
+    Single(&'static [u8]),
+    // Compiled from the file on disk, so changes show up without a rebuild
+    #[cfg(feature = "hot-reload")]
+    File(PathBuf),
+}
+
+// Turns a shader's variants into shader modules when they're first asked
+// for, and hangs on to them so switching back to a variant is free
+pub struct ShaderVariants {
+    name: String,
+    stage: ShaderStage,
+    source: Source,
+    variants: HashMap<Defines, Variant>,
+}
+
+impl ShaderVariants {
+    // `variants` pairs the defines each variant was compiled with in build.rs
+    // with its spirv from `include_spirv!`
+    pub fn embedded(name: &str, stage: ShaderStage, variants: &[(&[&str], &'static [u8])]) -> Self {
+        let variants = variants
+            .iter()
+            .map(|&(defines, spirv)| (defines.iter().cloned().collect(), spirv))
+            .collect();
+        Self::with_source(name, stage, Source::Embedded(variants))
+    }
+
+    // For a shader that's the same whatever the key's defines are, like a
+    // fragment shader shared by every variant of the vertex shader
+    pub fn single(name: &str, stage: ShaderStage, spirv: &'static [u8]) -> Self {
+        Self::with_source(name, stage, Source::Single(spirv))
+    }
+
+    // Compiles each variant from source the first time it's needed
+    #[cfg(feature = "hot-reload")]
+    pub fn from_file<P: Into<PathBuf>>(path: P, stage: ShaderStage) -> Self {
+        let path = path.into();
+        Self::with_source(&path.display().to_string(), stage, Source::File(path))
+    }
+
+    fn with_source(name: &str, stage: ShaderStage, source: Source) -> Self {
+        Self {
+            name: name.to_string(),
+            stage,
+            source,
+            variants: HashMap::new(),
+        }
+    }
+
+    pub fn get(&mut self, device: &wgpu::Device, defines: &Defines) -> Result<&Variant, ShaderError> {
+        // Every key shares the one variant of a single shader
+        let defines = match self.source {
+            Source::Single(_) => Defines::new(),
+            _ => defines.clone(),
+        };
+
+        if !self.variants.contains_key(&defines) {
+            let spirv = match &self.source {
+                Source::Embedded(variants) => match variants.get(&defines) {
+                    Some(spirv) => shader::read_spirv_bytes(&self.name, self.stage, spirv)?,
+                    None => {
+                        return Err(ShaderError {
+                            name: self.name.clone(),
+                            stage: Some(self.stage),
+                            kind: ShaderErrorKind::MissingVariant(defines),
+                        })
+                    }
+                },
+                Source::Single(spirv) => shader::read_spirv_bytes(&self.name, self.stage, spirv)?,
+                #[cfg(feature = "hot-reload")]
+                Source::File(path) => shader::load_shader_variant(path, self.stage, &defines)?,
+            };
+            let module = device.create_shader_module(&spirv);
+            self.variants.insert(defines.clone(), Variant { spirv, module });
+        }
+        Ok(&self.variants[&defines])
+    }
+
+    // Throws away every variant, say because the file changed on disk
+    pub fn clear(&mut self) {
+        self.variants.clear();
+    }
+}
+
+// Whatever a program uses to pick which version of a pipeline it wants. This
+// is usually a small struct of bools or an enum.
+pub trait VariantKey: Clone + Eq + Hash {
+    fn defines(&self) -> Defines;
+}
+
+// A render pipeline for each key, built from the vertex and fragment shader
+// variants the key's defines select
+pub struct PipelineVariants<K> {
+    vertex: ShaderVariants,
+    fragment: ShaderVariants,
+    pipelines: HashMap<K, wgpu::RenderPipeline>,
+}
+
+impl<K: VariantKey> PipelineVariants<K> {
+    pub fn new(vertex: ShaderVariants, fragment: ShaderVariants) -> Self {
+        Self {
+            vertex,
+            fragment,
+            pipelines: HashMap::new(),
+        }
+    }
+
+    // The vertex and fragment variants `key` picks, for when something like
+    // the pipeline layout has to be worked out from them before `prepare`
+    pub fn shaders(&mut self, device: &wgpu::Device, key: &K) -> Result<(&Variant, &Variant), ShaderError> {
+        let defines = key.defines();
+        let vertex = self.vertex.get(device, &defines)?;
+        let fragment = self.fragment.get(device, &defines)?;
+        Ok((vertex, fragment))
+    }
+
+    // Builds the pipeline for `key` if we don't have it yet. `create` gets the
+    // vertex and fragment variants, and does the actual
+    // `device.create_render_pipeline` since only the caller knows the rest of
+    // the pipeline's state.
+    pub fn prepare<F>(&mut self, device: &wgpu::Device, key: &K, create: F) -> Result<&wgpu::RenderPipeline, ShaderError>
+    where
+        F: FnOnce(&Variant, &Variant) -> wgpu::RenderPipeline,
+    {
+        if !self.pipelines.contains_key(key) {
+            let defines = key.defines();
+            let vertex = self.vertex.get(device, &defines)?;
+            let fragment = self.fragment.get(device, &defines)?;
+            let pipeline = create(vertex, fragment);
+            self.pipelines.insert(key.clone(), pipeline);
+        }
+        Ok(&self.pipelines[key])
+    }
+
+    // The pipeline for a key that's already been through `prepare`
+    pub fn get(&self, key: &K) -> Option<&wgpu::RenderPipeline> {
+        self.pipelines.get(key)
+    }
+
+    pub fn clear(&mut self) {
+        self.vertex.clear();
+        self.fragment.clear();
+        self.pipelines.clear();
+    }
+}
@@ -0,0 +1,85 @@
+// Describes how a type is laid out in a vertex buffer. Rather than writing
+// this by hand, derive it on a `#[repr(C)]` struct:
+//
+//     #[repr(C)]
+//     #[derive(Copy, Clone, VertexLayout)]
+//     #[step_mode = "instance"]
+//     struct InstanceRaw {
+//         #[location = 2]
+//         model: cgmath::Matrix4<f32>,
+//     }
+//
+// See framework-derive for the rest of what the derive understands.
+pub trait VertexLayout {
+    fn desc<'a>() -> wgpu::VertexBufferDescriptor<'a>;
+}
+
+pub use framework_derive::VertexLayout;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[repr(C)]
+    #[derive(Copy, Clone, VertexLayout)]
+    struct Vertex {
+        position: cgmath::Vector3<f32>,
+        #[format = "Uchar2Norm"]
+        tex_coords: [u8; 2],
+        // Starts at 16, after two bytes of padding
+        weight: f32,
+        normal: [f32; 3],
+    }
+
+    #[repr(C)]
+    #[derive(Copy, Clone, VertexLayout)]
+    #[step_mode = "instance"]
+    struct Instance {
+        #[location = 5]
+        model: cgmath::Matrix4<f32>,
+        color: [u16; 4],
+        id: u32,
+    }
+
+    fn attributes<T: VertexLayout>() -> Vec<(wgpu::BufferAddress, wgpu::VertexFormat, u32)> {
+        T::desc()
+            .attributes
+            .iter()
+            .map(|attribute| (attribute.offset, attribute.format, attribute.shader_location))
+            .collect()
+    }
+
+    #[test]
+    fn fields_are_laid_out_like_repr_c() {
+        let desc = Vertex::desc();
+        assert_eq!(desc.stride, 32);
+        assert_eq!(desc.step_mode, wgpu::InputStepMode::Vertex);
+        assert_eq!(
+            attributes::<Vertex>(),
+            [
+                (0, wgpu::VertexFormat::Float3, 0),
+                (12, wgpu::VertexFormat::Uchar2Norm, 1),
+                (16, wgpu::VertexFormat::Float, 2),
+                (20, wgpu::VertexFormat::Float3, 3),
+            ],
+        );
+    }
+
+    #[test]
+    fn matrices_take_a_location_per_column() {
+        let desc = Instance::desc();
+        assert_eq!(desc.stride, 76);
+        assert_eq!(desc.step_mode, wgpu::InputStepMode::Instance);
+        assert_eq!(
+            attributes::<Instance>(),
+            [
+                (0, wgpu::VertexFormat::Float4, 5),
+                (16, wgpu::VertexFormat::Float4, 6),
+                (32, wgpu::VertexFormat::Float4, 7),
+                (48, wgpu::VertexFormat::Float4, 8),
+                (64, wgpu::VertexFormat::Ushort4, 9),
+                (72, wgpu::VertexFormat::Uint, 10),
+            ],
+        );
+    }
+}
@@ -0,0 +1,4 @@
+// Compiles the scene shaders to spirv, so we don't need a shader compiler at runtime
+fn main() {
+    framework::build::compile_shaders(&["src"]);
+}
@@ -0,0 +1,17 @@
+#!/bin/sh
+# Renders every scene that has a reference image in golden/ and compares the
+# result against it. Failed renders and their diffs are written to
+# target/golden/.
+#
+# On machines without a gpu, install a software vulkan driver (lavapipe or
+# swiftshader) and point VK_ICD_FILENAMES at its icd json, eg.
+#
+#   VK_ICD_FILENAMES=/usr/share/vulkan/icd.d/lvp_icd.x86_64.json ./check-golden.sh
+#
+# To add or update a reference, render it with
+#
+#   cargo run --bin windowless -- --scene <name> --golden golden/<name>.png --bless
+set -e
+
+cd "$(dirname "$0")"
+cargo test --release --test golden -- --ignored "$@"
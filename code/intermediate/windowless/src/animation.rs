@@ -0,0 +1,42 @@
+use gif::SetParameter;
+use image::RgbaImage;
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+
+// "out/spin.png" -> "out/spin-0007.png"
+pub fn frame_path(output: &Path, frame: u32) -> PathBuf {
+    let stem = output
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("frame");
+    let file_name = match output.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => format!("{}-{:04}.{}", stem, frame, ext),
+        None => format!("{}-{:04}", stem, frame),
+    };
+    output.with_file_name(file_name)
+}
+
+pub struct GifWriter {
+    encoder: gif::Encoder<BufWriter<File>>,
+    // gifs store the time between frames in hundredths of a second
+    delay: u16,
+}
+
+impl GifWriter {
+    pub fn create(path: &Path, size: wgpu::Extent3d, time_step: f32) -> io::Result<Self> {
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = gif::Encoder::new(file, size.width as u16, size.height as u16, &[])?;
+        encoder.set(gif::Repeat::Infinite)?;
+        let delay = (time_step * 100.0).round().max(1.0) as u16;
+        Ok(Self { encoder, delay })
+    }
+
+    pub fn write_frame(&mut self, image: &RgbaImage) -> io::Result<()> {
+        let mut pixels = image.clone().into_raw();
+        // 10 is the speed gif recommends, lower is slower but picks better colors
+        let mut frame = gif::Frame::from_rgba_speed(image.width() as u16, image.height() as u16, &mut pixels, 10);
+        frame.delay = self.delay;
+        self.encoder.write_frame(&frame)
+    }
+}
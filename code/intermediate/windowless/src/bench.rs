@@ -0,0 +1,119 @@
+use std::time::{Duration, Instant};
+
+use crate::readback::ReadbackError;
+use crate::scene::Scene;
+use crate::target::RenderTarget;
+
+pub struct BenchSettings {
+    pub frames: u32,
+    // Frames drawn before we start timing, so pipeline and driver warm up isn't counted
+    pub warmup: u32,
+    pub readbacks: u32,
+    pub clear_color: wgpu::Color,
+}
+
+pub struct Stats {
+    pub min: Duration,
+    pub median: Duration,
+    pub mean: Duration,
+    pub max: Duration,
+}
+
+impl Stats {
+    pub fn new(samples: &mut [Duration]) -> Self {
+        assert!(!samples.is_empty());
+        samples.sort();
+        let total: Duration = samples.iter().sum();
+        Self {
+            min: samples[0],
+            median: samples[samples.len() / 2],
+            mean: total / samples.len() as u32,
+            max: samples[samples.len() - 1],
+        }
+    }
+}
+
+impl std::fmt::Display for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "min {:.3}ms, median {:.3}ms, mean {:.3}ms, max {:.3}ms",
+            millis(self.min),
+            millis(self.median),
+            millis(self.mean),
+            millis(self.max),
+        )
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+pub struct BenchResults {
+    // Time spent on the cpu recording and submitting each frame
+    pub submit: Stats,
+    // Time from the first submit until the gpu finished the last frame
+    pub wall_clock: Duration,
+    pub frames: u32,
+    pub readback: Option<Stats>,
+}
+
+impl BenchResults {
+    pub fn frames_per_second(&self) -> f64 {
+        self.frames as f64 / self.wall_clock.as_secs_f64()
+    }
+}
+
+pub fn run(
+    device: &wgpu::Device,
+    queue: &mut wgpu::Queue,
+    scene: &dyn Scene,
+    target: &RenderTarget,
+    settings: &BenchSettings,
+) -> Result<BenchResults, ReadbackError> {
+    let render_frame = |queue: &mut wgpu::Queue| {
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            todo: 0,
+        });
+        scene.render(&mut encoder, target, settings.clear_color);
+        queue.submit(&[encoder.finish()]);
+    };
+
+    for _ in 0..settings.warmup {
+        render_frame(queue);
+    }
+    device.poll(true);
+
+    let mut submit_times = Vec::with_capacity(settings.frames as usize);
+    let start = Instant::now();
+    for _ in 0..settings.frames {
+        let frame_start = Instant::now();
+        render_frame(queue);
+        submit_times.push(frame_start.elapsed());
+    }
+    // Blocks until the gpu is done with everything we submitted
+    device.poll(true);
+    let wall_clock = start.elapsed();
+
+    // Readback is timed on its own, after the gpu has gone idle, so it
+    // doesn't get mixed up with the render times
+    let mut readback_times = (0..settings.readbacks)
+        .map(|_| {
+            let readback_start = Instant::now();
+            target.read_bytes(device, queue)?;
+            Ok(readback_start.elapsed())
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(BenchResults {
+        submit: Stats::new(&mut submit_times),
+        wall_clock,
+        frames: settings.frames,
+        readback: if readback_times.is_empty() {
+            None
+        } else {
+            Some(Stats::new(&mut readback_times))
+        },
+    })
+}
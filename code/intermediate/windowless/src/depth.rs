@@ -0,0 +1,53 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+// The contents of a depth buffer, 0 at the near plane and 1 at the far plane
+pub struct DepthImage {
+    pub width: u32,
+    pub height: u32,
+    pub values: Vec<f32>,
+}
+
+impl DepthImage {
+    pub fn from_bytes(width: u32, height: u32, data: &[u8]) -> Self {
+        let values = data
+            .chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .collect();
+        Self { width, height, values }
+    }
+
+    pub fn range(&self) -> (f32, f32) {
+        self.values.iter().fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), &value| {
+            (min.min(value), max.max(value))
+        })
+    }
+
+    // Stretches the depth values that are actually in the image over the full
+    // 16 bits, so small differences near the far plane are still visible
+    pub fn save_png(&self, path: &Path) -> io::Result<()> {
+        let (min, max) = self.range();
+        let scale = if max > min { 1.0 / (max - min) } else { 0.0 };
+
+        let mut data = Vec::with_capacity(self.values.len() * 2);
+        for value in &self.values {
+            let normalized = ((value - min) * scale * 65535.0).round() as u16;
+            // png stores 16 bit samples as big endian
+            data.extend_from_slice(&normalized.to_be_bytes());
+        }
+
+        image::save_buffer_with_format(path, &data, self.width, self.height, image::Gray(16), image::ImageFormat::PNG)
+    }
+
+    // Little endian f32s, one row after another with no header
+    pub fn save_raw(&self, path: &Path) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        for value in &self.values {
+            file.write_all(&value.to_le_bytes())?;
+        }
+        file.flush()
+    }
+}
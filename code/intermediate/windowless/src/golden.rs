@@ -0,0 +1,181 @@
+use image::{Rgba, RgbaImage};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Copy, Clone)]
+pub struct Tolerance {
+    // How far apart any channel of two pixels can be before they count as different
+    pub channel: u8,
+    // The fraction of pixels that are allowed to differ
+    pub max_mismatch_ratio: f64,
+}
+
+pub struct Comparison {
+    pub mismatched_pixels: u64,
+    pub total_pixels: u64,
+    pub max_channel_difference: u8,
+    // Mismatched pixels are red, everything else is a faded copy of the reference
+    pub diff: RgbaImage,
+}
+
+impl Comparison {
+    pub fn mismatch_ratio(&self) -> f64 {
+        self.mismatched_pixels as f64 / self.total_pixels as f64
+    }
+
+    pub fn passed(&self, tolerance: &Tolerance) -> bool {
+        self.mismatch_ratio() <= tolerance.max_mismatch_ratio
+    }
+}
+
+pub fn compare(actual: &RgbaImage, reference: &RgbaImage, tolerance: &Tolerance) -> Result<Comparison, String> {
+    if actual.dimensions() != reference.dimensions() {
+        return Err(format!(
+            "image is {:?} but the reference is {:?}",
+            actual.dimensions(),
+            reference.dimensions(),
+        ));
+    }
+
+    let (width, height) = reference.dimensions();
+    let mut diff = RgbaImage::new(width, height);
+    let mut mismatched_pixels = 0;
+    let mut max_channel_difference = 0;
+
+    for (x, y, expected) in reference.enumerate_pixels() {
+        let found = actual.get_pixel(x, y);
+        let difference = expected.0.iter()
+            .zip(found.0.iter())
+            .map(|(a, b)| a.max(b) - a.min(b))
+            .max()
+            .unwrap();
+        max_channel_difference = max_channel_difference.max(difference);
+
+        let diff_pixel = if difference > tolerance.channel {
+            mismatched_pixels += 1;
+            Rgba([255, 0, 0, 255])
+        } else {
+            let [r, g, b, _] = expected.0;
+            let luma = ((r as u32 + g as u32 + b as u32) / 3 / 4) as u8;
+            Rgba([luma, luma, luma, 255])
+        };
+        diff.put_pixel(x, y, diff_pixel);
+    }
+
+    Ok(Comparison {
+        mismatched_pixels,
+        total_pixels: width as u64 * height as u64,
+        max_channel_difference,
+        diff,
+    })
+}
+
+// "out/triangle.png" -> "out/triangle-diff.png"
+pub fn diff_path(actual_path: &Path) -> PathBuf {
+    let stem = actual_path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("image");
+    actual_path.with_file_name(format!("{}-diff.png", stem))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(width: u32, height: u32, color: [u8; 4]) -> RgbaImage {
+        RgbaImage::from_pixel(width, height, Rgba(color))
+    }
+
+    const EXACT: Tolerance = Tolerance {
+        channel: 0,
+        max_mismatch_ratio: 0.0,
+    };
+
+    #[test]
+    fn identical_images_pass() {
+        let image = solid(4, 4, [10, 20, 30, 255]);
+        let comparison = compare(&image, &image, &EXACT).unwrap();
+        assert_eq!(comparison.mismatched_pixels, 0);
+        assert_eq!(comparison.total_pixels, 16);
+        assert_eq!(comparison.max_channel_difference, 0);
+        assert!(comparison.passed(&EXACT));
+    }
+
+    #[test]
+    fn differences_up_to_the_channel_tolerance_match() {
+        let reference = solid(2, 2, [100, 100, 100, 255]);
+        let tolerance = Tolerance {
+            channel: 3,
+            max_mismatch_ratio: 0.0,
+        };
+
+        let within = solid(2, 2, [103, 97, 100, 255]);
+        let comparison = compare(&within, &reference, &tolerance).unwrap();
+        assert_eq!(comparison.mismatched_pixels, 0);
+        assert_eq!(comparison.max_channel_difference, 3);
+        assert!(comparison.passed(&tolerance));
+
+        let outside = solid(2, 2, [100, 100, 104, 255]);
+        let comparison = compare(&outside, &reference, &tolerance).unwrap();
+        assert_eq!(comparison.mismatched_pixels, 4);
+        assert_eq!(comparison.max_channel_difference, 4);
+        assert!(!comparison.passed(&tolerance));
+    }
+
+    #[test]
+    fn alpha_counts_as_a_channel() {
+        let reference = solid(1, 1, [0, 0, 0, 255]);
+        let actual = solid(1, 1, [0, 0, 0, 0]);
+        let comparison = compare(&actual, &reference, &EXACT).unwrap();
+        assert_eq!(comparison.mismatched_pixels, 1);
+        assert_eq!(comparison.max_channel_difference, 255);
+    }
+
+    #[test]
+    fn mismatch_ratio_is_checked_inclusively() {
+        // 1 of 10 pixels is off
+        let reference = solid(10, 1, [0, 0, 0, 255]);
+        let mut actual = reference.clone();
+        actual.put_pixel(3, 0, Rgba([255, 0, 0, 255]));
+
+        let comparison = compare(&actual, &reference, &EXACT).unwrap();
+        assert_eq!(comparison.mismatched_pixels, 1);
+        assert!((comparison.mismatch_ratio() - 0.1).abs() < 1e-12);
+
+        let at_limit = Tolerance {
+            channel: 0,
+            max_mismatch_ratio: 0.1,
+        };
+        assert!(comparison.passed(&at_limit));
+
+        let below_limit = Tolerance {
+            channel: 0,
+            max_mismatch_ratio: 0.09,
+        };
+        assert!(!comparison.passed(&below_limit));
+    }
+
+    #[test]
+    fn diff_marks_mismatched_pixels_red() {
+        let reference = solid(2, 1, [200, 200, 200, 255]);
+        let mut actual = reference.clone();
+        actual.put_pixel(1, 0, Rgba([0, 0, 0, 255]));
+
+        let comparison = compare(&actual, &reference, &EXACT).unwrap();
+        assert_eq!(*comparison.diff.get_pixel(1, 0), Rgba([255, 0, 0, 255]));
+        // Matching pixels are the reference's brightness, faded to a quarter
+        assert_eq!(*comparison.diff.get_pixel(0, 0), Rgba([50, 50, 50, 255]));
+    }
+
+    #[test]
+    fn different_sizes_are_an_error() {
+        let reference = solid(4, 4, [0, 0, 0, 255]);
+        let actual = solid(4, 5, [0, 0, 0, 255]);
+        assert!(compare(&actual, &reference, &EXACT).is_err());
+    }
+
+    #[test]
+    fn diff_path_goes_next_to_the_output() {
+        assert_eq!(diff_path(Path::new("out/triangle.png")), Path::new("out/triangle-diff.png"));
+    }
+}
@@ -0,0 +1,94 @@
+use image::{Rgb, Rgba, RgbaImage};
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+
+pub fn is_float_format(format: wgpu::TextureFormat) -> bool {
+    matches!(format, wgpu::TextureFormat::Rgba16Float | wgpu::TextureFormat::Rgba32Float)
+}
+
+pub fn decode_float_texels(format: wgpu::TextureFormat, data: &[u8]) -> Vec<[f32; 4]> {
+    match format {
+        wgpu::TextureFormat::Rgba16Float => data
+            .chunks_exact(8)
+            .map(|texel| {
+                let mut pixel = [0.0; 4];
+                for (channel, bytes) in pixel.iter_mut().zip(texel.chunks_exact(2)) {
+                    let bits = u16::from_le_bytes([bytes[0], bytes[1]]);
+                    *channel = half::f16::from_bits(bits).to_f32();
+                }
+                pixel
+            })
+            .collect(),
+        wgpu::TextureFormat::Rgba32Float => data
+            .chunks_exact(16)
+            .map(|texel| {
+                let mut pixel = [0.0; 4];
+                for (channel, bytes) in pixel.iter_mut().zip(texel.chunks_exact(4)) {
+                    *channel = f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                }
+                pixel
+            })
+            .collect(),
+        _ => panic!("{:?} is not a floating point format", format),
+    }
+}
+
+// Linear, unclamped colors read back from a floating point render target
+pub struct HdrImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<[f32; 4]>,
+}
+
+impl HdrImage {
+    // `data` is tightly packed texels in `format`, which must be one of the float formats
+    pub fn from_bytes(format: wgpu::TextureFormat, width: u32, height: u32, data: &[u8]) -> Self {
+        let pixels = decode_float_texels(format, data);
+        Self { width, height, pixels }
+    }
+
+    // Squashes the colors into 0 to 1 with the Reinhard operator and encodes them as srgb
+    pub fn tonemap(&self, exposure: f32) -> RgbaImage {
+        let mut image = RgbaImage::new(self.width, self.height);
+        for (pixel, [r, g, b, a]) in image.pixels_mut().zip(self.pixels.iter()) {
+            let map = |c: f32| {
+                let c = (c * exposure).max(0.0);
+                to_u8(linear_to_srgb(c / (1.0 + c)))
+            };
+            *pixel = Rgba([map(*r), map(*g), map(*b), to_u8(*a)]);
+        }
+        image
+    }
+
+    pub fn save_exr(&self, path: &Path) -> io::Result<()> {
+        let width = self.width as usize;
+        exr::prelude::write_rgba_file(path, width, self.height as usize, |x, y| {
+            let [r, g, b, a] = self.pixels[y * width + x];
+            (r, g, b, a)
+        })
+        .map_err(|e| io::Error::other(e.to_string()))
+    }
+
+    // Radiance files don't have an alpha channel
+    pub fn save_radiance(&self, path: &Path) -> io::Result<()> {
+        let file = BufWriter::new(File::create(path)?);
+        let pixels = self.pixels
+            .iter()
+            .map(|[r, g, b, _]| Rgb([r.max(0.0), g.max(0.0), b.max(0.0)]))
+            .collect::<Vec<_>>();
+        image::hdr::HDREncoder::new(file).encode(&pixels, self.width as usize, self.height as usize)
+    }
+}
+
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+pub fn to_u8(c: f32) -> u8 {
+    (c.clamp(0.0, 1.0) * 255.0).round() as u8
+}
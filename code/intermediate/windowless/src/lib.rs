@@ -0,0 +1,12 @@
+// Everything that doesn't depend on the command line lives here, so the
+// golden image tests in tests/ can render scenes the same way the binary does
+
+pub mod animation;
+pub mod bench;
+pub mod depth;
+pub mod golden;
+pub mod hdr;
+pub mod output;
+pub mod readback;
+pub mod scene;
+pub mod target;
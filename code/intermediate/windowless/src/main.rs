@@ -1,140 +1,222 @@
+mod options;
+
+use image::RgbaImage;
+use std::path::Path;
+use structopt::StructOpt;
+
+use framework::staging::StagingRing;
+use windowless::hdr::{self, HdrImage};
+use windowless::output::OutputFormat;
+use windowless::target::RenderTarget;
+use windowless::{animation, bench, golden, scene};
+
+use options::Options;
+
+// Plenty for the instances of any of the scenes. Bigger writes get a staging
+// buffer of their own.
+const STAGING_CHUNK_SIZE: wgpu::BufferAddress = 64 * 1024;
+
 fn main() {
-    let adapter = wgpu::Adapter::request(&wgpu::RequestAdapterOptions {
-        ..Default::default()
-    }).unwrap();
-    let (device, mut queue) = adapter.request_device(&Default::default());
+    let options = Options::from_args();
+    let output_format = match options.output_format() {
+        Ok(format) => format,
+        Err(e) => exit_with_error(e),
+    };
+    if output_format.is_hdr() && !hdr::is_float_format(options.format.0) {
+        exit_with_error(format!("saving {:?} images needs a float --format", output_format));
+    }
+    if options.saves_depth() && options.samples > 1 {
+        exit_with_error("multisampled depth can't be saved, use --samples 1");
+    }
 
-    let texture_size = 256u32;
-    let texture_desc = wgpu::TextureDescriptor {
-        size: wgpu::Extent3d {
-            width: texture_size,
-            height: texture_size,
-            depth: 1,
+    // When checking against a reference we render at the reference's size
+    let reference = match &options.golden {
+        Some(path) if !options.bless => match image::open(path) {
+            Ok(reference) => Some(reference.to_rgba()),
+            Err(e) => exit_with_error(format!(
+                "couldn't open reference image {:?}: {} (use --bless to create it)",
+                path, e,
+            )),
         },
-        array_layer_count: 1,
-        mip_level_count: 1,
-        sample_count: 1,
-        dimension: wgpu::TextureDimension::D2,
-        format: wgpu::TextureFormat::Rgba8UnormSrgb,
-        usage: wgpu::TextureUsage::COPY_SRC,
+        _ => None,
     };
-    let texture = device.create_texture(&texture_desc);
-    let texture_view = texture.create_default_view();
-
-    let u32_size = std::mem::size_of::<u32>() as u32;
-    let output_buffer_size = (u32_size * texture_size * texture_size) as wgpu::BufferAddress;
-    let output_buffer_desc = wgpu::BufferDescriptor {
-        size: output_buffer_size,
-        usage: wgpu::BufferUsage::COPY_DST 
-            | wgpu::BufferUsage::MAP_READ
-            ,
+    let size = match &reference {
+        Some(reference) => wgpu::Extent3d {
+            width: reference.width(),
+            height: reference.height(),
+            depth: 1,
+        },
+        None => options.texture_size(),
     };
-    let output_buffer = device.create_buffer(&output_buffer_desc);
-
-    let vs_src = include_str!("shader.vert");
-    let fs_src = include_str!("shader.frag");
-    let vs_spirv = glsl_to_spirv::compile(vs_src, glsl_to_spirv::ShaderType::Vertex).unwrap();
-    let fs_spirv = glsl_to_spirv::compile(fs_src, glsl_to_spirv::ShaderType::Fragment).unwrap();
-    let vs_data = wgpu::read_spirv(vs_spirv).unwrap();
-    let fs_data = wgpu::read_spirv(fs_spirv).unwrap();
-    let vs_module = device.create_shader_module(&vs_data);
-    let fs_module = device.create_shader_module(&fs_data);
-
-    let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-        bind_group_layouts: &[],
+
+    let adapter = wgpu::Adapter::request(&wgpu::RequestAdapterOptions {
+        backends: options.backend.0,
+        ..Default::default()
+    }).unwrap_or_else(|| exit_with_error("no suitable adapter found"));
+    let (device, mut queue) = adapter.request_device(&Default::default());
+
+    let target = RenderTarget::new(&device, size, options.format.0, options.samples);
+    let mut scene = options.scene.create(&device, &mut queue, &target).unwrap_or_else(|e| exit_with_error(e));
+
+    if let Some(frames) = options.bench {
+        run_bench(&options, frames, &device, &mut queue, &*scene, &target);
+        return;
+    }
+
+    let mut gif = options.gif.as_ref().map(|path| {
+        animation::GifWriter::create(path, size, options.time_step)
+            .unwrap_or_else(|e| exit_with_error(format!("couldn't create {:?}: {}", path, e)))
     });
 
-    let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-        layout: &render_pipeline_layout,
-        vertex_stage: wgpu::ProgrammableStageDescriptor {
-            module: &vs_module,
-            entry_point: "main",
-        },
-        fragment_stage: Some(wgpu::ProgrammableStageDescriptor {
-            module: &fs_module,
-            entry_point: "main",
-        }),
-        rasterization_state: Some(wgpu::RasterizationStateDescriptor {
-            front_face: wgpu::FrontFace::Ccw,
-            cull_mode: wgpu::CullMode::Back,
-            depth_bias: 0,
-            depth_bias_slope_scale: 0.0,
-            depth_bias_clamp: 0.0,
-        }),
-        primitive_topology: wgpu::PrimitiveTopology::TriangleList,
-        color_states: &[
-            wgpu::ColorStateDescriptor {
-                format: texture_desc.format,
-                color_blend: wgpu::BlendDescriptor::REPLACE,
-                alpha_blend: wgpu::BlendDescriptor::REPLACE,
-                write_mask: wgpu::ColorWrite::ALL,
+    // The scenes' updates go through here, so the same staging memory gets
+    // used every frame
+    let mut staging_ring = StagingRing::new(STAGING_CHUNK_SIZE);
+
+    for frame in 0..options.frames {
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            todo: 0,
+        });
+        // The first frame shows the scene as it starts
+        if frame > 0 {
+            scene.update(&device, &mut encoder, &mut staging_ring, options.time_step);
+        }
+        staging_ring.finish();
+        scene.render(&mut encoder, &target, options.clear_color.0);
+        queue.submit(&[encoder.finish()]);
+        staging_ring.recall(&device);
+
+        let (image, hdr) = if target.is_hdr() {
+            let hdr = target.read_hdr(&device, &mut queue).unwrap_or_else(|e| exit_with_error(e));
+            (hdr.tonemap(options.exposure), Some(hdr))
+        } else {
+            (target.read(&device, &mut queue).unwrap_or_else(|e| exit_with_error(e)), None)
+        };
+
+        let frame_path = |path: &Path| if options.frames == 1 {
+            path.to_path_buf()
+        } else {
+            animation::frame_path(path, frame)
+        };
+
+        match (&options.golden, &reference) {
+            (Some(path), None) => {
+                save(&image, OutputFormat::Png, path);
+                println!("blessed {:?}", path);
+            }
+            (Some(path), Some(reference)) => check_golden(&options, path, &image, reference),
+            (None, _) => match &hdr {
+                Some(hdr) if output_format.is_hdr() => save_hdr(hdr, output_format, &frame_path(&options.output)),
+                _ => save(&image, output_format, &frame_path(&options.output)),
             },
-        ],
-        depth_stencil_state: None,
-        index_format: wgpu::IndexFormat::Uint16,
-        vertex_buffers: &[],
-        sample_count: 1,
-        sample_mask: !0,
-        alpha_to_coverage_enabled: false,
-    });
+        }
 
-    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-        todo: 0,
-    });
+        if options.saves_depth() {
+            let depth = target.read_depth(&device, &mut queue).unwrap_or_else(|e| exit_with_error(e));
+            let (min, max) = depth.range();
+            println!("depth range {} to {}", min, max);
 
-    {
-        let render_pass_desc = wgpu::RenderPassDescriptor {
-            color_attachments: &[
-                wgpu::RenderPassColorAttachmentDescriptor {
-                    attachment: &texture_view,
-                    resolve_target: None,
-                    load_op: wgpu::LoadOp::Clear,
-                    store_op: wgpu::StoreOp::Store,
-                    clear_color: wgpu::Color {
-                        r: 0.1,
-                        g: 0.2,
-                        b: 0.3,
-                        a: 1.0,
-                    },
+            if let Some(path) = &options.depth {
+                let path = frame_path(path);
+                if let Err(e) = depth.save_png(&path) {
+                    exit_with_error(format!("couldn't save {:?}: {}", path, e));
                 }
-            ],
-            depth_stencil_attachment: None,
-        };
-        let mut render_pass = encoder.begin_render_pass(&render_pass_desc);
+            }
+            if let Some(path) = &options.depth_raw {
+                let path = frame_path(path);
+                if let Err(e) = depth.save_raw(&path) {
+                    exit_with_error(format!("couldn't save {:?}: {}", path, e));
+                }
+            }
+        }
+
+        if let Some(preview) = &options.preview {
+            save(&image, OutputFormat::Png, &frame_path(preview));
+        }
+
+        if let Some(gif) = &mut gif {
+            if let Err(e) = gif.write_frame(&image) {
+                exit_with_error(format!("couldn't write gif frame: {}", e));
+            }
+        }
+    }
+}
 
-        render_pass.set_pipeline(&render_pipeline);
-        render_pass.draw(0..3, 0..1);
+fn run_bench(
+    options: &Options,
+    frames: u32,
+    device: &wgpu::Device,
+    queue: &mut wgpu::Queue,
+    scene: &dyn scene::Scene,
+    target: &RenderTarget,
+) {
+    if frames == 0 {
+        exit_with_error("--bench needs at least one frame");
     }
 
-    encoder.copy_texture_to_buffer(
-        wgpu::TextureCopyView {
-            texture: &texture,
-            mip_level: 0,
-            array_layer: 0,
-            origin: wgpu::Origin3d::ZERO,
-        }, 
-        wgpu::BufferCopyView {
-            buffer: &output_buffer,
-            offset: 0,
-            row_pitch: u32_size * texture_size,
-            image_height: texture_size,
-        }, 
-        texture_desc.size,
+    let settings = bench::BenchSettings {
+        frames,
+        warmup: options.warmup,
+        readbacks: options.readbacks,
+        clear_color: options.clear_color.0,
+    };
+    let results = bench::run(device, queue, scene, target, &settings).unwrap_or_else(|e| exit_with_error(e));
+
+    println!(
+        "{:?} at {}x{}, {:?}, {} sample(s)",
+        options.scene, target.size.width, target.size.height, target.format, target.sample_count,
+    );
+    println!("submit:    {}", results.submit);
+    println!(
+        "rendering: {} frames in {:.3}s, {:.1} frames per second",
+        results.frames,
+        results.wall_clock.as_secs_f64(),
+        results.frames_per_second(),
+    );
+    if let Some(readback) = &results.readback {
+        println!("readback:  {}", readback);
+    }
+}
+
+fn check_golden(options: &Options, path: &Path, image: &RgbaImage, reference: &RgbaImage) {
+    let tolerance = options.golden_tolerance();
+    let comparison = match golden::compare(image, reference, &tolerance) {
+        Ok(comparison) => comparison,
+        Err(e) => exit_with_error(e),
+    };
+
+    println!(
+        "{:?}: {} of {} pixels differ ({:.4}%), largest channel difference {}",
+        path,
+        comparison.mismatched_pixels,
+        comparison.total_pixels,
+        comparison.mismatch_ratio() * 100.0,
+        comparison.max_channel_difference,
     );
 
-    queue.submit(&[encoder.finish()]);
+    if !comparison.passed(&tolerance) {
+        let diff_path = golden::diff_path(&options.output);
+        save(image, OutputFormat::Png, &options.output);
+        save(&comparison.diff, OutputFormat::Png, &diff_path);
+        exit_with_error(format!(
+            "render doesn't match the reference, wrote {:?} and {:?}",
+            options.output, diff_path,
+        ));
+    }
+}
 
-    output_buffer.map_read_async(0, output_buffer_size, move |result: wgpu::BufferMapAsyncResult<&[u8]>| {
-        let mapping = result.unwrap();
-        let data = mapping.data;
+fn save(image: &RgbaImage, format: OutputFormat, path: &Path) {
+    if let Err(e) = format.save(path, image, image.width(), image.height()) {
+        exit_with_error(format!("couldn't save {:?}: {}", path, e));
+    }
+}
 
-        use image::{ImageBuffer, Rgba};
-        let buffer = ImageBuffer::<Rgba<u8>, _>::from_raw(
-            texture_size,
-            texture_size,
-            data,
-        ).unwrap();
+fn save_hdr(image: &HdrImage, format: OutputFormat, path: &Path) {
+    if let Err(e) = format.save_hdr(path, image) {
+        exit_with_error(format!("couldn't save {:?}: {}", path, e));
+    }
+}
 
-        buffer.save("image.png").unwrap();
-    });
-}
\ No newline at end of file
+fn exit_with_error<E: std::fmt::Display>(e: E) -> ! {
+    eprintln!("error: {}", e);
+    std::process::exit(1);
+}
@@ -0,0 +1,211 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+use structopt::StructOpt;
+
+use windowless::golden::Tolerance;
+use windowless::output::OutputFormat;
+use windowless::readback::MAX_TEXTURE_DIMENSION;
+use windowless::scene::SceneName;
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "windowless", about = "Renders to an image without opening a window")]
+pub struct Options {
+    /// The scene to draw [possible values: triangle, instanced, shapes, model, gltf]
+    #[structopt(long, default_value = "triangle")]
+    pub scene: SceneName,
+
+    /// Width of the render target in pixels
+    #[structopt(long, default_value = "256", parse(try_from_str = parse_dimension))]
+    pub width: u32,
+
+    /// Height of the render target in pixels
+    #[structopt(long, default_value = "256", parse(try_from_str = parse_dimension))]
+    pub height: u32,
+
+    /// Where to save the rendered image
+    #[structopt(short, long, default_value = "image.png", parse(from_os_str))]
+    pub output: PathBuf,
+
+    /// Color the texture is cleared to, as "r,g,b" or "r,g,b,a" in the range 0 to 1
+    #[structopt(long, default_value = "0.1,0.2,0.3,1.0")]
+    pub clear_color: ClearColor,
+
+    /// Format of the render target [possible values: rgba8unorm-srgb, rgba8unorm, bgra8unorm-srgb, bgra8unorm,
+    /// rgba16float, rgba32float]. Images are saved srgb encoded, so linear formats get encoded on the way out.
+    /// Float formats are tonemapped with the Reinhard operator after --exposure, unless saved as exr or hdr
+    #[structopt(long, default_value = "rgba8unorm-srgb")]
+    pub format: TargetFormat,
+
+    /// Format of the saved image [possible values: png, jpeg, bmp, ppm, exr, hdr].
+    /// Guessed from the extension of the output path when left out. Exr and hdr need a float --format
+    #[structopt(long)]
+    pub image_format: Option<OutputFormat>,
+
+    /// Number of samples per pixel for anti-aliasing [possible values: 1, 2, 4, 8]
+    #[structopt(long, default_value = "1", possible_values = &["1", "2", "4", "8"])]
+    pub samples: u32,
+
+    /// Save the depth buffer as a 16 bit grayscale png, stretched over the range of depths in view.
+    /// Needs --samples 1
+    #[structopt(long, parse(from_os_str))]
+    pub depth: Option<PathBuf>,
+
+    /// Save the depth buffer as raw little endian f32s, one row after another
+    #[structopt(long, parse(from_os_str))]
+    pub depth_raw: Option<PathBuf>,
+
+    /// Brightness multiplier applied before tonemapping float render targets down to 8 bits
+    #[structopt(long, default_value = "1.0")]
+    pub exposure: f32,
+
+    /// Also save a tonemapped png of each frame here
+    #[structopt(long, parse(from_os_str))]
+    pub preview: Option<PathBuf>,
+
+    /// Number of frames to render. With more than one, each frame is saved next to --output
+    /// with its number appended, eg. image-0001.png
+    #[structopt(long, default_value = "1")]
+    pub frames: u32,
+
+    /// Seconds of simulated time between frames
+    #[structopt(long, default_value = "0.05")]
+    pub time_step: f32,
+
+    /// Also save the frames as an animated gif
+    #[structopt(long, parse(from_os_str))]
+    pub gif: Option<PathBuf>,
+
+    /// Instead of saving anything, draw the scene this many times and report how long it took
+    #[structopt(long, conflicts_with_all = &["frames", "gif", "golden"])]
+    pub bench: Option<u32>,
+
+    /// Frames drawn before the benchmark starts timing
+    #[structopt(long, default_value = "3")]
+    pub warmup: u32,
+
+    /// How many times the benchmark times reading the render target back to the cpu
+    #[structopt(long, default_value = "10")]
+    pub readbacks: u32,
+
+    /// Which graphics apis to look for an adapter on [possible values: primary, secondary, vulkan, metal, dx12, dx11, gl].
+    /// Use vulkan with VK_ICD_FILENAMES pointing at a software driver to render without a gpu
+    #[structopt(long, default_value = "primary")]
+    pub backend: Backend,
+
+    /// Compare the render against this reference image instead of saving it. The image is
+    /// rendered at the size of the reference, and on failure the render and a diff image are
+    /// written next to --output
+    #[structopt(long, parse(from_os_str), conflicts_with_all = &["frames", "gif"])]
+    pub golden: Option<PathBuf>,
+
+    /// Overwrite the --golden reference image with this render
+    #[structopt(long, requires = "golden")]
+    pub bless: bool,
+
+    /// How far apart a channel can be from the reference before the pixel counts as mismatched
+    #[structopt(long, default_value = "2")]
+    pub tolerance: u8,
+
+    /// The fraction of pixels that can mismatch the reference before the comparison fails
+    #[structopt(long, default_value = "0.001")]
+    pub max_mismatch: f64,
+}
+
+impl Options {
+    pub fn texture_size(&self) -> wgpu::Extent3d {
+        wgpu::Extent3d {
+            width: self.width,
+            height: self.height,
+            depth: 1,
+        }
+    }
+
+    pub fn saves_depth(&self) -> bool {
+        self.depth.is_some() || self.depth_raw.is_some()
+    }
+
+    pub fn golden_tolerance(&self) -> Tolerance {
+        Tolerance {
+            channel: self.tolerance,
+            max_mismatch_ratio: self.max_mismatch,
+        }
+    }
+
+    pub fn output_format(&self) -> Result<OutputFormat, String> {
+        match self.image_format {
+            Some(format) => Ok(format),
+            None => OutputFormat::from_path(&self.output),
+        }
+    }
+}
+
+fn parse_dimension(s: &str) -> Result<u32, String> {
+    let value = s.parse::<u32>().map_err(|e| e.to_string())?;
+    if value == 0 || value > MAX_TEXTURE_DIMENSION {
+        return Err(format!("must be between 1 and {}", MAX_TEXTURE_DIMENSION));
+    }
+    Ok(value)
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct ClearColor(pub wgpu::Color);
+
+impl FromStr for ClearColor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s
+            .split(',')
+            .map(|v| v.trim().parse::<f64>().map_err(|e| format!("{:?}: {}", v, e)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let (r, g, b, a) = match values[..] {
+            [r, g, b] => (r, g, b, 1.0),
+            [r, g, b, a] => (r, g, b, a),
+            _ => return Err(format!("expected 3 or 4 comma separated values, got {}", values.len())),
+        };
+
+        Ok(ClearColor(wgpu::Color { r, g, b, a }))
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct TargetFormat(pub wgpu::TextureFormat);
+
+impl FromStr for TargetFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let format = match s.to_ascii_lowercase().as_str() {
+            "rgba8unorm-srgb" => wgpu::TextureFormat::Rgba8UnormSrgb,
+            "rgba8unorm" => wgpu::TextureFormat::Rgba8Unorm,
+            "bgra8unorm-srgb" => wgpu::TextureFormat::Bgra8UnormSrgb,
+            "bgra8unorm" => wgpu::TextureFormat::Bgra8Unorm,
+            "rgba16float" => wgpu::TextureFormat::Rgba16Float,
+            "rgba32float" => wgpu::TextureFormat::Rgba32Float,
+            _ => return Err(format!("unsupported texture format {:?}", s)),
+        };
+        Ok(TargetFormat(format))
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Backend(pub wgpu::BackendBit);
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let backends = match s.to_ascii_lowercase().as_str() {
+            "primary" => wgpu::BackendBit::PRIMARY,
+            "secondary" => wgpu::BackendBit::SECONDARY,
+            "vulkan" => wgpu::BackendBit::VULKAN,
+            "metal" => wgpu::BackendBit::METAL,
+            "dx12" => wgpu::BackendBit::DX12,
+            "dx11" => wgpu::BackendBit::DX11,
+            "gl" => wgpu::BackendBit::GL,
+            _ => return Err(format!("unknown backend {:?}", s)),
+        };
+        Ok(Backend(backends))
+    }
+}
@@ -0,0 +1,91 @@
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::hdr::HdrImage;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum OutputFormat {
+    Png,
+    Jpeg,
+    Bmp,
+    Ppm,
+    Exr,
+    Hdr,
+}
+
+impl OutputFormat {
+    pub fn from_path(path: &Path) -> Result<Self, String> {
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .ok_or_else(|| format!("can't guess the image format of {:?}, use --image-format", path))?;
+        extension.parse()
+    }
+
+    // Whether this format stores floating point colors
+    pub fn is_hdr(self) -> bool {
+        matches!(self, OutputFormat::Exr | OutputFormat::Hdr)
+    }
+
+    pub fn save_hdr(self, path: &Path, image: &HdrImage) -> io::Result<()> {
+        match self {
+            OutputFormat::Exr => image.save_exr(path),
+            OutputFormat::Hdr => image.save_radiance(path),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{:?} images can't store floating point colors", self),
+            )),
+        }
+    }
+
+    // `data` is tightly packed RGBA with 8 bits per channel
+    pub fn save(self, path: &Path, data: &[u8], width: u32, height: u32) -> io::Result<()> {
+        match self {
+            OutputFormat::Png => image::save_buffer_with_format(
+                path, data, width, height, image::RGBA(8), image::ImageFormat::PNG,
+            ),
+            OutputFormat::Bmp => image::save_buffer_with_format(
+                path, data, width, height, image::RGBA(8), image::ImageFormat::BMP,
+            ),
+            // jpeg and ppm have no alpha channel, so we drop it
+            OutputFormat::Jpeg => image::save_buffer_with_format(
+                path, &rgba_to_rgb(data), width, height, image::RGB(8), image::ImageFormat::JPEG,
+            ),
+            OutputFormat::Ppm => {
+                use image::pnm::{PNMEncoder, PNMSubtype, SampleEncoding};
+                let mut file = BufWriter::new(File::create(path)?);
+                PNMEncoder::new(&mut file)
+                    .with_subtype(PNMSubtype::Pixmap(SampleEncoding::Binary))
+                    .encode(&rgba_to_rgb(data)[..], width, height, image::RGB(8))
+            }
+            OutputFormat::Exr | OutputFormat::Hdr => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{:?} images need a floating point render target", self),
+            )),
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "png" => Ok(OutputFormat::Png),
+            "jpg" | "jpeg" => Ok(OutputFormat::Jpeg),
+            "bmp" => Ok(OutputFormat::Bmp),
+            "ppm" => Ok(OutputFormat::Ppm),
+            "exr" => Ok(OutputFormat::Exr),
+            "hdr" => Ok(OutputFormat::Hdr),
+            _ => Err(format!("unsupported image format {:?}", s)),
+        }
+    }
+}
+
+fn rgba_to_rgb(data: &[u8]) -> Vec<u8> {
+    data.chunks_exact(4)
+        .flat_map(|pixel| pixel[..3].iter().cloned())
+        .collect()
+}
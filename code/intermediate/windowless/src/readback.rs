@@ -0,0 +1,279 @@
+use image::RgbaImage;
+use std::fmt;
+use std::sync::mpsc;
+
+use crate::hdr;
+
+// wgpu requires the rows of a texture to buffer copy to start on a multiple of this
+pub const COPY_ROW_ALIGNMENT: u32 = 256;
+
+// wgpu 0.4 doesn't tell us how big a texture the adapter can make, so widths
+// and heights stop at 8192, the default 2d texture limit every WebGPU
+// implementation has to support
+pub const MAX_TEXTURE_DIMENSION: u32 = 8192;
+
+#[derive(Debug)]
+pub enum ReadbackError {
+    UnsupportedFormat(wgpu::TextureFormat),
+    MapFailed,
+}
+
+impl fmt::Display for ReadbackError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReadbackError::UnsupportedFormat(format) => write!(
+                f,
+                "can't read back {:?} textures, supported formats are rgba8/bgra8 (unorm or srgb), rgba16float, rgba32float and depth32float",
+                format,
+            ),
+            ReadbackError::MapFailed => write!(f, "couldn't map the readback buffer"),
+        }
+    }
+}
+
+impl std::error::Error for ReadbackError {}
+
+// How many bytes one texel of `format` takes up, for the formats we know how to read
+pub fn texel_size(format: wgpu::TextureFormat) -> Result<u32, ReadbackError> {
+    match format {
+        wgpu::TextureFormat::Rgba8Unorm
+        | wgpu::TextureFormat::Rgba8UnormSrgb
+        | wgpu::TextureFormat::Bgra8Unorm
+        | wgpu::TextureFormat::Bgra8UnormSrgb
+        | wgpu::TextureFormat::Depth32Float => Ok(4),
+        wgpu::TextureFormat::Rgba16Float => Ok(8),
+        wgpu::TextureFormat::Rgba32Float => Ok(16),
+        _ => Err(ReadbackError::UnsupportedFormat(format)),
+    }
+}
+
+// Errors for the formats `to_rgba8` can't convert, so we can find out before
+// copying anything. Depth formats have a texel size but no colors.
+pub fn check_supported(format: wgpu::TextureFormat) -> Result<(), ReadbackError> {
+    match format {
+        wgpu::TextureFormat::Rgba8Unorm
+        | wgpu::TextureFormat::Rgba8UnormSrgb
+        | wgpu::TextureFormat::Bgra8Unorm
+        | wgpu::TextureFormat::Bgra8UnormSrgb
+        | wgpu::TextureFormat::Rgba16Float
+        | wgpu::TextureFormat::Rgba32Float => Ok(()),
+        _ => Err(ReadbackError::UnsupportedFormat(format)),
+    }
+}
+
+// Converts tightly packed texels in `format` into srgb encoded 8 bit rgba, for
+// saving. Srgb formats already are, so they keep their bytes (bgra just gets
+// its channels swapped around). Linear unorm colors get encoded, or they'd
+// come out too dark. Float colors are clamped to 0 to 1 and encoded, which
+// loses anything brighter than 1; the binary tonemaps float targets with
+// `HdrImage::tonemap` instead, so they keep some detail in the highlights.
+pub fn to_rgba8(format: wgpu::TextureFormat, data: &[u8]) -> Result<Vec<u8>, ReadbackError> {
+    check_supported(format)?;
+    let mut rgba = match format {
+        wgpu::TextureFormat::Rgba16Float | wgpu::TextureFormat::Rgba32Float => {
+            return Ok(hdr::decode_float_texels(format, data)
+                .into_iter()
+                .flat_map(|[r, g, b, a]| {
+                    let encode = |c: f32| hdr::to_u8(hdr::linear_to_srgb(c.clamp(0.0, 1.0)));
+                    vec![encode(r), encode(g), encode(b), hdr::to_u8(a)]
+                })
+                .collect());
+        }
+        _ => data.to_vec(),
+    };
+
+    if let wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb = format {
+        for texel in rgba.chunks_exact_mut(4) {
+            texel.swap(0, 2);
+        }
+    }
+    if let wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Bgra8Unorm = format {
+        // Alpha isn't a color, so it stays linear
+        for texel in rgba.chunks_exact_mut(4) {
+            for c in &mut texel[..3] {
+                *c = hdr::to_u8(hdr::linear_to_srgb(*c as f32 / 255.0));
+            }
+        }
+    }
+    Ok(rgba)
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct BufferDimensions {
+    pub height: u32,
+    pub unpadded_bytes_per_row: u32,
+    pub padded_bytes_per_row: u32,
+}
+
+impl BufferDimensions {
+    pub fn new(width: u32, height: u32, bytes_per_pixel: u32) -> Self {
+        let unpadded_bytes_per_row = width * bytes_per_pixel;
+        let padding = (COPY_ROW_ALIGNMENT - unpadded_bytes_per_row % COPY_ROW_ALIGNMENT) % COPY_ROW_ALIGNMENT;
+        let padded_bytes_per_row = unpadded_bytes_per_row + padding;
+        Self {
+            height,
+            unpadded_bytes_per_row,
+            padded_bytes_per_row,
+        }
+    }
+
+    pub fn buffer_size(&self) -> wgpu::BufferAddress {
+        self.padded_bytes_per_row as wgpu::BufferAddress * self.height as wgpu::BufferAddress
+    }
+
+    // Copies the rows out of a padded buffer so they sit right next to each other
+    pub fn strip_padding(&self, padded: &[u8]) -> Vec<u8> {
+        let mut data = Vec::with_capacity(self.unpadded_bytes_per_row as usize * self.height as usize);
+        for row in padded
+            .chunks(self.padded_bytes_per_row as usize)
+            .take(self.height as usize)
+        {
+            data.extend_from_slice(&row[..self.unpadded_bytes_per_row as usize]);
+        }
+        data
+    }
+}
+
+// Copies a whole texture into cpu memory and returns the tightly packed bytes.
+// This blocks until the gpu has finished all the work submitted before it.
+pub fn read_texture_bytes(
+    device: &wgpu::Device,
+    queue: &mut wgpu::Queue,
+    texture: &wgpu::Texture,
+    size: wgpu::Extent3d,
+    format: wgpu::TextureFormat,
+) -> Result<Vec<u8>, ReadbackError> {
+    let dimensions = BufferDimensions::new(size.width, size.height, texel_size(format)?);
+
+    let output_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        size: dimensions.buffer_size(),
+        usage: wgpu::BufferUsage::COPY_DST | wgpu::BufferUsage::MAP_READ,
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        todo: 0,
+    });
+
+    encoder.copy_texture_to_buffer(
+        wgpu::TextureCopyView {
+            texture,
+            mip_level: 0,
+            array_layer: 0,
+            origin: wgpu::Origin3d::ZERO,
+        },
+        wgpu::BufferCopyView {
+            buffer: &output_buffer,
+            offset: 0,
+            row_pitch: dimensions.padded_bytes_per_row,
+            image_height: size.height,
+        },
+        size,
+    );
+
+    queue.submit(&[encoder.finish()]);
+
+    // The callback has to be 'static, so we send the data back through a channel
+    let (sender, receiver) = mpsc::channel();
+    output_buffer.map_read_async(0, dimensions.buffer_size(), move |result: wgpu::BufferMapAsyncResult<&[u8]>| {
+        let data = result.map(|mapping| dimensions.strip_padding(mapping.data));
+        sender.send(data).unwrap();
+    });
+
+    // Waits for the copy to finish, which runs the callback above
+    device.poll(true);
+
+    receiver.recv().unwrap().map_err(|_| ReadbackError::MapFailed)
+}
+
+// Reads a color texture in any of the formats `to_rgba8` understands
+pub fn read_texture(
+    device: &wgpu::Device,
+    queue: &mut wgpu::Queue,
+    texture: &wgpu::Texture,
+    size: wgpu::Extent3d,
+    format: wgpu::TextureFormat,
+) -> Result<RgbaImage, ReadbackError> {
+    check_supported(format)?;
+    let data = read_texture_bytes(device, queue, texture, size, format)?;
+    let rgba = to_rgba8(format, &data)?;
+    Ok(RgbaImage::from_raw(size.width, size.height, rgba).unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIDTHS: &[u32] = &[1, 63, 64, 65, MAX_TEXTURE_DIMENSION];
+
+    #[test]
+    fn rows_are_padded_to_the_copy_alignment() {
+        for &width in WIDTHS {
+            for &bytes_per_pixel in &[4, 8, 16] {
+                let dimensions = BufferDimensions::new(width, 3, bytes_per_pixel);
+                let unpadded = width * bytes_per_pixel;
+                assert_eq!(dimensions.unpadded_bytes_per_row, unpadded);
+                assert_eq!(dimensions.padded_bytes_per_row % COPY_ROW_ALIGNMENT, 0, "width {}", width);
+                assert!(dimensions.padded_bytes_per_row >= unpadded);
+                assert!(dimensions.padded_bytes_per_row - unpadded < COPY_ROW_ALIGNMENT);
+                assert_eq!(dimensions.buffer_size(), dimensions.padded_bytes_per_row as u64 * 3);
+            }
+        }
+    }
+
+    #[test]
+    fn rows_that_fit_exactly_get_no_padding() {
+        let dimensions = BufferDimensions::new(64, 1, 4);
+        assert_eq!(dimensions.padded_bytes_per_row, 256);
+        let dimensions = BufferDimensions::new(65, 1, 4);
+        assert_eq!(dimensions.padded_bytes_per_row, 512);
+    }
+
+    #[test]
+    fn strip_padding_keeps_every_row_in_order() {
+        let height = 3;
+        for &width in WIDTHS {
+            let dimensions = BufferDimensions::new(width, height, 4);
+            let padded_row = dimensions.padded_bytes_per_row as usize;
+            let row = dimensions.unpadded_bytes_per_row as usize;
+
+            // Each row's texels count up from the row number, and the padding is 0xff
+            let mut padded = vec![0xff; dimensions.buffer_size() as usize];
+            for y in 0..height as usize {
+                for x in 0..row {
+                    padded[y * padded_row + x] = (y + x) as u8;
+                }
+            }
+
+            let data = dimensions.strip_padding(&padded);
+            assert_eq!(data.len(), row * height as usize, "width {}", width);
+            for (y, texels) in data.chunks(row).enumerate() {
+                assert!(texels.iter().enumerate().all(|(x, &b)| b == (y + x) as u8), "width {} row {}", width, y);
+            }
+        }
+    }
+
+    #[test]
+    fn srgb_texels_are_saved_as_rendered() {
+        let texels = [0, 64, 128, 255, 10, 20, 30, 40];
+        assert_eq!(to_rgba8(wgpu::TextureFormat::Rgba8UnormSrgb, &texels).unwrap(), texels);
+        assert_eq!(
+            to_rgba8(wgpu::TextureFormat::Bgra8UnormSrgb, &texels).unwrap(),
+            [128, 64, 0, 255, 30, 20, 10, 40],
+        );
+    }
+
+    #[test]
+    fn linear_texels_are_srgb_encoded() {
+        // Linear 0.5 is srgb 188, and the ends stay where they are
+        let texels = [0, 128, 255, 128];
+        assert_eq!(to_rgba8(wgpu::TextureFormat::Rgba8Unorm, &texels).unwrap(), [0, 188, 255, 128]);
+        assert_eq!(to_rgba8(wgpu::TextureFormat::Bgra8Unorm, &texels).unwrap(), [255, 188, 0, 128]);
+    }
+
+    #[test]
+    fn depth_textures_are_not_colors() {
+        assert!(texel_size(wgpu::TextureFormat::Depth32Float).is_ok());
+        assert!(check_supported(wgpu::TextureFormat::Depth32Float).is_err());
+        assert!(to_rgba8(wgpu::TextureFormat::Depth32Float, &[0; 4]).is_err());
+    }
+}
@@ -0,0 +1,85 @@
+use framework::gltf::Scene as GltfScene;
+use framework::instance::InstanceRaw;
+use framework::mesh::Vertex;
+use framework::pipeline::IndexFormatPipelines;
+use framework::shader::ShaderError;
+use framework::texture::Texture;
+use framework::vertex::VertexLayout;
+
+use super::instanced;
+use super::Scene;
+use crate::target::RenderTarget;
+
+// Two glTF files drawn with the instanced pipeline: a .gltf with its buffer
+// and texture in separate files, and a .glb with everything packed inside.
+// Every node that uses a mesh becomes one of its instances.
+
+const GLTF_PATHS: &[&str] = &[
+    concat!(env!("CARGO_MANIFEST_DIR"), "/src/scene/trees.gltf"),
+    concat!(env!("CARGO_MANIFEST_DIR"), "/src/scene/crate.glb"),
+];
+
+pub struct Gltf {
+    // One for each index format the meshes use
+    render_pipelines: IndexFormatPipelines,
+
+    scenes: Vec<GltfScene>,
+    uniform_bind_group: wgpu::BindGroup,
+}
+
+impl Gltf {
+    pub fn new(device: &wgpu::Device, queue: &mut wgpu::Queue, target: &RenderTarget) -> Result<Self, ShaderError> {
+        let texture_bind_group_layout = Texture::create_bind_group_layout(device);
+        let (uniform_bind_group_layout, uniform_bind_group) = instanced::create_uniform_bind_group(device, target);
+
+        // The files ship with the crate, so not finding them is a bug
+        let scenes = GLTF_PATHS
+            .iter()
+            .map(|path| GltfScene::load(device, queue, path, &texture_bind_group_layout).unwrap())
+            .collect::<Vec<_>>();
+
+        let mut render_pipelines = IndexFormatPipelines::new();
+        let primitives = scenes.iter().flat_map(|scene| &scene.meshes).flat_map(|mesh| &mesh.primitives);
+        for primitive in primitives {
+            render_pipelines.prepare(primitive.index_format, |index_format| {
+                instanced::create_render_pipeline(
+                    device,
+                    target,
+                    &[&texture_bind_group_layout, &uniform_bind_group_layout],
+                    &[Vertex::desc(), InstanceRaw::desc()],
+                    index_format,
+                )
+            })?;
+        }
+
+        Ok(Self {
+            render_pipelines,
+            scenes,
+            uniform_bind_group,
+        })
+    }
+}
+
+impl Scene for Gltf {
+    fn render(&self, encoder: &mut wgpu::CommandEncoder, target: &RenderTarget, clear_color: wgpu::Color) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            color_attachments: &[target.color_attachment(clear_color)],
+            depth_stencil_attachment: Some(target.depth_attachment()),
+        });
+
+        for scene in &self.scenes {
+            for mesh in &scene.meshes {
+                for primitive in &mesh.primitives {
+                    let render_pipeline = self.render_pipelines.get(primitive.index_format).unwrap();
+
+                    render_pass.set_pipeline(render_pipeline);
+                    render_pass.set_bind_group(0, &scene.materials[primitive.material].bind_group, &[]);
+                    render_pass.set_bind_group(1, &self.uniform_bind_group, &[]);
+                    render_pass.set_vertex_buffers(0, &[(&primitive.vertex_buffer, 0), (&mesh.instance_buffer, 0)]);
+                    render_pass.set_index_buffer(&primitive.index_buffer, 0);
+                    render_pass.draw_indexed(0..primitive.num_indices, 0, 0..mesh.num_instances);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,336 @@
+use cgmath::prelude::*;
+
+use framework::camera::{Camera, Uniforms};
+use framework::instance::{self, Instance, InstanceRaw};
+use framework::mesh::{Indices, Mesh, Vertex};
+use framework::shader::{create_spirv_module, ShaderError, ShaderStage};
+use framework::staging::StagingRing;
+use framework::vertex::VertexLayout;
+
+use super::Scene;
+use crate::target::RenderTarget;
+
+// The forest of pentagons from the instancing tutorial, drawn with the same
+// camera, uniforms, instance buffer and texture. The shapes scene borrows all
+// of that too.
+
+const VERTICES: &[Vertex] = &[
+    Vertex { position: [-0.0868241, -0.49240386, 0.0], tex_coords: [1.0 - 0.4131759, 1.0 - 0.00759614], }, // A
+    Vertex { position: [-0.49513406, -0.06958647, 0.0], tex_coords: [1.0 - 0.0048659444, 1.0 - 0.43041354], }, // B
+    Vertex { position: [-0.21918549, 0.44939706, 0.0], tex_coords: [1.0 - 0.28081453, 1.0 - 0.949397], }, // C
+    Vertex { position: [0.35966998, 0.3473291, 0.0], tex_coords: [1.0 - 0.85967, 1.0 - 0.84732911], }, // D
+    Vertex { position: [0.44147372, -0.2347359, 0.0], tex_coords: [1.0 - 0.9414737, 1.0 - 0.2652641], }, // E
+];
+
+const INDICES: &[u32] = &[
+    0, 1, 4,
+    1, 2, 4,
+    2, 3, 4,
+];
+
+const NUM_INSTANCES_PER_ROW: u32 = 10;
+const INSTANCE_DISPLACEMENT: cgmath::Vector3<f32> = cgmath::Vector3::new(NUM_INSTANCES_PER_ROW as f32 * 0.5, 0.0, NUM_INSTANCES_PER_ROW as f32 * 0.5);
+
+// One full turn every 1.2 seconds, the same speed as the instancing challenge
+const ROTATION_SPEED: f32 = 2.0 * std::f32::consts::PI / 1.2;
+
+pub struct Instanced {
+    render_pipeline: wgpu::RenderPipeline,
+
+    mesh: Mesh,
+
+    diffuse_bind_group: wgpu::BindGroup,
+    uniform_bind_group: wgpu::BindGroup,
+
+    instances: Vec<Instance>,
+    instance_buffer: wgpu::Buffer,
+}
+
+impl Instanced {
+    pub fn new(device: &wgpu::Device, queue: &mut wgpu::Queue, target: &RenderTarget) -> Result<Self, ShaderError> {
+        let (texture_bind_group_layout, diffuse_bind_group) = create_diffuse_bind_group(device, queue);
+        let (uniform_bind_group_layout, uniform_bind_group) = create_uniform_bind_group(device, target);
+
+        let instances = (0..NUM_INSTANCES_PER_ROW).flat_map(|z| {
+            (0..NUM_INSTANCES_PER_ROW).map(move |x| {
+                let position = cgmath::Vector3 { x: x as f32, y: 0.0, z: z as f32 } - INSTANCE_DISPLACEMENT;
+
+                let rotation = if position.is_zero() {
+                    // this is needed so an object at (0, 0, 0) won't get scaled to zero
+                    // as Quaternions can effect scale if they're not create correctly
+                    cgmath::Quaternion::from_axis_angle(cgmath::Vector3::unit_z(), cgmath::Deg(0.0))
+                } else {
+                    cgmath::Quaternion::from_axis_angle(position.normalize(), cgmath::Deg(45.0))
+                };
+
+                Instance {
+                    position, rotation,
+                }
+            })
+        }).collect::<Vec<_>>();
+
+        let instance_data = instances.iter().map(Instance::to_raw).collect::<Vec<_>>();
+        let instance_buffer = instance::create_instance_buffer(device, &instance_data);
+
+        let mesh = Mesh::new(device, VERTICES, &Indices::new(INDICES, VERTICES.len()));
+
+        let render_pipeline = create_render_pipeline(
+            device,
+            target,
+            &[&texture_bind_group_layout, &uniform_bind_group_layout],
+            &[Vertex::desc(), InstanceRaw::desc()],
+            mesh.index_format,
+        )?;
+
+        Ok(Self {
+            render_pipeline,
+            mesh,
+            diffuse_bind_group,
+            uniform_bind_group,
+            instances,
+            instance_buffer,
+        })
+    }
+}
+
+// The happy tree texture, bound at set 0
+pub(super) fn create_diffuse_bind_group(device: &wgpu::Device, queue: &mut wgpu::Queue) -> (wgpu::BindGroupLayout, wgpu::BindGroup) {
+    let diffuse_bytes = include_bytes!("happy-tree.png");
+    let diffuse_image = image::load_from_memory(diffuse_bytes).unwrap();
+    let diffuse_rgba = diffuse_image.as_rgba8().unwrap();
+
+    use image::GenericImageView;
+    let dimensions = diffuse_image.dimensions();
+
+    let size3d = wgpu::Extent3d {
+        width: dimensions.0,
+        height: dimensions.1,
+        depth: 1,
+    };
+    let diffuse_texture = device.create_texture(&wgpu::TextureDescriptor {
+        size: size3d,
+        array_layer_count: 1,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8UnormSrgb,
+        usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+    });
+
+    let diffuse_buffer = device
+        .create_buffer_mapped(diffuse_rgba.len(), wgpu::BufferUsage::COPY_SRC)
+        .fill_from_slice(diffuse_rgba);
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        todo: 0,
+    });
+
+    encoder.copy_buffer_to_texture(
+        wgpu::BufferCopyView {
+            buffer: &diffuse_buffer,
+            offset: 0,
+            row_pitch: 4 * dimensions.0,
+            image_height: dimensions.1,
+        },
+        wgpu::TextureCopyView {
+            texture: &diffuse_texture,
+            mip_level: 0,
+            array_layer: 0,
+            origin: wgpu::Origin3d::ZERO,
+        },
+        size3d,
+    );
+
+    queue.submit(&[encoder.finish()]);
+
+    let diffuse_texture_view = diffuse_texture.create_default_view();
+    let diffuse_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+        address_mode_u: wgpu::AddressMode::ClampToEdge,
+        address_mode_v: wgpu::AddressMode::ClampToEdge,
+        address_mode_w: wgpu::AddressMode::ClampToEdge,
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Nearest,
+        mipmap_filter: wgpu::FilterMode::Nearest,
+        lod_min_clamp: -100.0,
+        lod_max_clamp: 100.0,
+        compare_function: wgpu::CompareFunction::Always,
+    });
+
+    let texture_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        bindings: &[
+            wgpu::BindGroupLayoutBinding {
+                binding: 0,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::SampledTexture {
+                    multisampled: false,
+                    dimension: wgpu::TextureViewDimension::D2,
+                },
+            },
+            wgpu::BindGroupLayoutBinding {
+                binding: 1,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::Sampler,
+            },
+        ],
+    });
+
+    let diffuse_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &texture_bind_group_layout,
+        bindings: &[
+            wgpu::Binding {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&diffuse_texture_view),
+            },
+            wgpu::Binding {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&diffuse_sampler),
+            }
+        ],
+    });
+
+    (texture_bind_group_layout, diffuse_bind_group)
+}
+
+// The camera's view projection matrix, bound at set 1
+pub(super) fn create_uniform_bind_group(device: &wgpu::Device, target: &RenderTarget) -> (wgpu::BindGroupLayout, wgpu::BindGroup) {
+    let camera = Camera {
+        eye: (0.0, 5.0, -10.0).into(),
+        target: (0.0, 0.0, 0.0).into(),
+        up: cgmath::Vector3::unit_y(),
+        aspect: target.size.width as f32 / target.size.height as f32,
+        fovy: 45.0,
+        znear: 0.1,
+        zfar: 100.0,
+    };
+
+    let mut uniforms = Uniforms::new();
+    uniforms.update_view_proj(&camera);
+
+    let uniform_buffer = device
+        .create_buffer_mapped(1, wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST)
+        .fill_from_slice(&[uniforms]);
+
+    let uniform_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        bindings: &[
+            wgpu::BindGroupLayoutBinding {
+                binding: 0,
+                visibility: wgpu::ShaderStage::VERTEX,
+                ty: wgpu::BindingType::UniformBuffer {
+                    dynamic: false,
+                },
+            },
+        ]
+    });
+
+    let uniform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &uniform_bind_group_layout,
+        bindings: &[
+            wgpu::Binding {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer {
+                    buffer: &uniform_buffer,
+                    range: 0..std::mem::size_of_val(&uniforms) as wgpu::BufferAddress,
+                }
+            },
+        ],
+    });
+
+    (uniform_bind_group_layout, uniform_bind_group)
+}
+
+// A pipeline for instanced.vert and instanced.frag. The shaders want a vertex
+// with a position and texture coordinates, and an `InstanceRaw` after it.
+pub(super) fn create_render_pipeline(
+    device: &wgpu::Device,
+    target: &RenderTarget,
+    bind_group_layouts: &[&wgpu::BindGroupLayout],
+    vertex_buffers: &[wgpu::VertexBufferDescriptor],
+    index_format: wgpu::IndexFormat,
+) -> Result<wgpu::RenderPipeline, ShaderError> {
+    let vs_spirv = framework::include_spirv!("src/scene/instanced.vert");
+    let fs_spirv = framework::include_spirv!("src/scene/instanced.frag");
+    let vs_module = create_spirv_module(device, "instanced.vert", ShaderStage::Vertex, vs_spirv)?;
+    let fs_module = create_spirv_module(device, "instanced.frag", ShaderStage::Fragment, fs_spirv)?;
+
+    let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        bind_group_layouts,
+    });
+
+    Ok(device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        layout: &render_pipeline_layout,
+        vertex_stage: wgpu::ProgrammableStageDescriptor {
+            module: &vs_module,
+            entry_point: "main",
+        },
+        fragment_stage: Some(wgpu::ProgrammableStageDescriptor {
+            module: &fs_module,
+            entry_point: "main",
+        }),
+        rasterization_state: Some(wgpu::RasterizationStateDescriptor {
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: wgpu::CullMode::Back,
+            depth_bias: 0,
+            depth_bias_slope_scale: 0.0,
+            depth_bias_clamp: 0.0,
+        }),
+        primitive_topology: wgpu::PrimitiveTopology::TriangleList,
+        color_states: &[
+            wgpu::ColorStateDescriptor {
+                format: target.format,
+                color_blend: wgpu::BlendDescriptor::REPLACE,
+                alpha_blend: wgpu::BlendDescriptor::REPLACE,
+                write_mask: wgpu::ColorWrite::ALL,
+            },
+        ],
+        depth_stencil_state: Some(target.depth_stencil_state()),
+        index_format,
+        vertex_buffers,
+        sample_count: target.sample_count,
+        sample_mask: !0,
+        alpha_to_coverage_enabled: false,
+    }))
+}
+
+// Spins every instance around the y axis
+pub(super) fn update_instances(
+    device: &wgpu::Device,
+    encoder: &mut wgpu::CommandEncoder,
+    staging_ring: &mut StagingRing,
+    instances: &mut [Instance],
+    instance_buffer: &wgpu::Buffer,
+    dt: f32,
+) {
+    let amount = cgmath::Quaternion::from_angle_y(cgmath::Rad(ROTATION_SPEED * dt));
+    for instance in instances.iter_mut() {
+        instance.rotation = amount * instance.rotation;
+    }
+
+    let instance_data = instances.iter().map(Instance::to_raw).collect::<Vec<_>>();
+    staging_ring.write(device, encoder, instance_buffer, 0, &instance_data);
+}
+
+impl Scene for Instanced {
+    fn update(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        staging_ring: &mut StagingRing,
+        dt: f32,
+    ) {
+        update_instances(device, encoder, staging_ring, &mut self.instances, &self.instance_buffer, dt);
+    }
+
+    fn render(&self, encoder: &mut wgpu::CommandEncoder, target: &RenderTarget, clear_color: wgpu::Color) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            color_attachments: &[target.color_attachment(clear_color)],
+            depth_stencil_attachment: Some(target.depth_attachment()),
+        });
+
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, &self.diffuse_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.uniform_bind_group, &[]);
+        render_pass.set_vertex_buffers(0, &[(&self.mesh.vertex_buffer, 0), (&self.instance_buffer, 0)]);
+        render_pass.set_index_buffer(&self.mesh.index_buffer, 0);
+        render_pass.draw_indexed(0..self.mesh.num_indices, 0, 0..self.instances.len() as u32);
+    }
+}
@@ -0,0 +1,91 @@
+use std::str::FromStr;
+
+use framework::shader::ShaderError;
+use framework::staging::StagingRing;
+
+use crate::target::RenderTarget;
+
+mod gltf;
+mod instanced;
+mod model;
+mod shapes;
+mod triangle;
+
+pub use gltf::Gltf;
+pub use instanced::Instanced;
+pub use model::Model;
+pub use shapes::Shapes;
+pub use triangle::Triangle;
+
+pub trait Scene {
+    // Moves the scene forward by `dt` seconds. Buffers it changes are written
+    // through `staging_ring`, which gets finished before `encoder` is submitted.
+    fn update(
+        &mut self,
+        _device: &wgpu::Device,
+        _encoder: &mut wgpu::CommandEncoder,
+        _staging_ring: &mut StagingRing,
+        _dt: f32,
+    ) {
+    }
+
+    fn render(&self, encoder: &mut wgpu::CommandEncoder, target: &RenderTarget, clear_color: wgpu::Color);
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SceneName {
+    Triangle,
+    Instanced,
+    Shapes,
+    Model,
+    Gltf,
+}
+
+impl SceneName {
+    pub const ALL: &'static [SceneName] = &[
+        SceneName::Triangle,
+        SceneName::Instanced,
+        SceneName::Shapes,
+        SceneName::Model,
+        SceneName::Gltf,
+    ];
+
+    // What the scene is called on the command line and in golden/
+    pub fn name(self) -> &'static str {
+        match self {
+            SceneName::Triangle => "triangle",
+            SceneName::Instanced => "instanced",
+            SceneName::Shapes => "shapes",
+            SceneName::Model => "model",
+            SceneName::Gltf => "gltf",
+        }
+    }
+
+    pub fn create(
+        self,
+        device: &wgpu::Device,
+        queue: &mut wgpu::Queue,
+        target: &RenderTarget,
+    ) -> Result<Box<dyn Scene>, ShaderError> {
+        Ok(match self {
+            SceneName::Triangle => Box::new(Triangle::new(device, target)?),
+            SceneName::Instanced => Box::new(Instanced::new(device, queue, target)?),
+            SceneName::Shapes => Box::new(Shapes::new(device, queue, target)?),
+            SceneName::Model => Box::new(Model::new(device, queue, target)?),
+            SceneName::Gltf => Box::new(Gltf::new(device, queue, target)?),
+        })
+    }
+}
+
+impl FromStr for SceneName {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.to_ascii_lowercase();
+        SceneName::ALL
+            .iter()
+            .cloned()
+            .find(|scene| scene.name() == name)
+            .ok_or_else(|| format!("unknown scene {:?}", s))
+    }
+}
@@ -0,0 +1,115 @@
+use framework::instance::{self, Instance, InstanceRaw};
+use framework::mesh::Vertex;
+use framework::obj::Model as ObjModel;
+use framework::pipeline::IndexFormatPipelines;
+use framework::shader::ShaderError;
+use framework::staging::StagingRing;
+use framework::vertex::VertexLayout;
+
+use super::instanced;
+use super::Scene;
+use crate::target::RenderTarget;
+
+// A model loaded from an OBJ file, spinning in the middle of the screen
+
+const MODEL_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/scene/cube.obj");
+
+pub struct Model {
+    // One for each index format the model's meshes use
+    render_pipelines: IndexFormatPipelines,
+
+    model: ObjModel,
+    // One for each of the model's materials, or `None` if it has no texture
+    material_bind_groups: Vec<Option<wgpu::BindGroup>>,
+    // For meshes without a textured material
+    fallback_bind_group: wgpu::BindGroup,
+    uniform_bind_group: wgpu::BindGroup,
+
+    instances: Vec<Instance>,
+    instance_buffer: wgpu::Buffer,
+}
+
+impl Model {
+    pub fn new(device: &wgpu::Device, queue: &mut wgpu::Queue, target: &RenderTarget) -> Result<Self, ShaderError> {
+        let (texture_bind_group_layout, fallback_bind_group) = instanced::create_diffuse_bind_group(device, queue);
+        let (uniform_bind_group_layout, uniform_bind_group) = instanced::create_uniform_bind_group(device, target);
+
+        // The model ships with the crate, so not finding it is a bug
+        let model = ObjModel::load(device, queue, MODEL_PATH).unwrap();
+        let material_bind_groups = model
+            .materials
+            .iter()
+            .map(|material| {
+                material
+                    .diffuse_texture
+                    .as_ref()
+                    // The same layout as the happy tree, so one pipeline draws both
+                    .map(|texture| texture.create_bind_group(device, &texture_bind_group_layout))
+            })
+            .collect();
+
+        let instances = vec![Instance {
+            position: cgmath::Vector3 { x: 0.0, y: 0.0, z: 0.0 },
+            rotation: cgmath::Quaternion::new(1.0, 0.0, 0.0, 0.0),
+        }];
+        let instance_data = instances.iter().map(Instance::to_raw).collect::<Vec<_>>();
+        let instance_buffer = instance::create_instance_buffer(device, &instance_data);
+
+        let mut render_pipelines = IndexFormatPipelines::new();
+        for mesh in &model.meshes {
+            render_pipelines.prepare(mesh.index_format, |index_format| {
+                instanced::create_render_pipeline(
+                    device,
+                    target,
+                    &[&texture_bind_group_layout, &uniform_bind_group_layout],
+                    &[Vertex::desc(), InstanceRaw::desc()],
+                    index_format,
+                )
+            })?;
+        }
+
+        Ok(Self {
+            render_pipelines,
+            model,
+            material_bind_groups,
+            fallback_bind_group,
+            uniform_bind_group,
+            instances,
+            instance_buffer,
+        })
+    }
+}
+
+impl Scene for Model {
+    fn update(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        staging_ring: &mut StagingRing,
+        dt: f32,
+    ) {
+        instanced::update_instances(device, encoder, staging_ring, &mut self.instances, &self.instance_buffer, dt);
+    }
+
+    fn render(&self, encoder: &mut wgpu::CommandEncoder, target: &RenderTarget, clear_color: wgpu::Color) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            color_attachments: &[target.color_attachment(clear_color)],
+            depth_stencil_attachment: Some(target.depth_attachment()),
+        });
+
+        for mesh in &self.model.meshes {
+            let render_pipeline = self.render_pipelines.get(mesh.index_format).unwrap();
+            let diffuse_bind_group = mesh
+                .material
+                .and_then(|material| self.material_bind_groups[material].as_ref())
+                .unwrap_or(&self.fallback_bind_group);
+
+            render_pass.set_pipeline(render_pipeline);
+            render_pass.set_bind_group(0, diffuse_bind_group, &[]);
+            render_pass.set_bind_group(1, &self.uniform_bind_group, &[]);
+            render_pass.set_vertex_buffers(0, &[(&mesh.vertex_buffer, 0), (&self.instance_buffer, 0)]);
+            render_pass.set_index_buffer(&mesh.index_buffer, 0);
+            render_pass.draw_indexed(0..mesh.num_indices, 0, 0..self.instances.len() as u32);
+        }
+    }
+}
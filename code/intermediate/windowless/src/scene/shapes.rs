@@ -0,0 +1,111 @@
+use framework::instance::{self, Instance, InstanceRaw};
+use framework::mesh::{Mesh, Vertex};
+use framework::pipeline::IndexFormatPipelines;
+use framework::shader::ShaderError;
+use framework::shapes;
+use framework::staging::StagingRing;
+use framework::vertex::VertexLayout;
+
+use super::instanced;
+use super::Scene;
+use crate::target::RenderTarget;
+
+// Every shape from `framework::shapes` in a row, spinning like the instanced
+// pentagons so you can see all the way around them
+
+const SPACING: f32 = 1.5;
+
+pub struct Shapes {
+    render_pipelines: IndexFormatPipelines,
+
+    shapes: Vec<Mesh>,
+
+    diffuse_bind_group: wgpu::BindGroup,
+    uniform_bind_group: wgpu::BindGroup,
+
+    // One instance per shape
+    instances: Vec<Instance>,
+    instance_buffer: wgpu::Buffer,
+}
+
+impl Shapes {
+    pub fn new(device: &wgpu::Device, queue: &mut wgpu::Queue, target: &RenderTarget) -> Result<Self, ShaderError> {
+        let (texture_bind_group_layout, diffuse_bind_group) = instanced::create_diffuse_bind_group(device, queue);
+        let (uniform_bind_group_layout, uniform_bind_group) = instanced::create_uniform_bind_group(device, target);
+
+        let meshes = [
+            shapes::regular_polygon(5, 0.5),
+            shapes::circle(0.5, 32),
+            shapes::quad(1.0, 1.0),
+            shapes::grid(1.0, 1.0, 4, 4),
+            shapes::cube(0.8),
+            shapes::uv_sphere(0.5, 24, 16),
+            shapes::cylinder(0.4, 1.0, 24),
+            shapes::torus(0.4, 0.15, 32, 16),
+        ];
+        let shapes = meshes.iter().map(|mesh| mesh.create_mesh(device)).collect::<Vec<_>>();
+
+        // The camera sits at -z, and from there +x is on the left
+        let first = (shapes.len() - 1) as f32 * SPACING / 2.0;
+        let instances = (0..shapes.len())
+            .map(|i| Instance {
+                position: cgmath::Vector3 { x: first - i as f32 * SPACING, y: 0.0, z: 0.0 },
+                rotation: cgmath::Quaternion::new(1.0, 0.0, 0.0, 0.0),
+            })
+            .collect::<Vec<_>>();
+
+        let instance_data = instances.iter().map(Instance::to_raw).collect::<Vec<_>>();
+        let instance_buffer = instance::create_instance_buffer(device, &instance_data);
+
+        let mut render_pipelines = IndexFormatPipelines::new();
+        for shape in &shapes {
+            render_pipelines.prepare(shape.index_format, |index_format| {
+                instanced::create_render_pipeline(
+                    device,
+                    target,
+                    &[&texture_bind_group_layout, &uniform_bind_group_layout],
+                    &[Vertex::desc(), InstanceRaw::desc()],
+                    index_format,
+                )
+            })?;
+        }
+
+        Ok(Self {
+            render_pipelines,
+            shapes,
+            diffuse_bind_group,
+            uniform_bind_group,
+            instances,
+            instance_buffer,
+        })
+    }
+}
+
+impl Scene for Shapes {
+    fn update(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        staging_ring: &mut StagingRing,
+        dt: f32,
+    ) {
+        instanced::update_instances(device, encoder, staging_ring, &mut self.instances, &self.instance_buffer, dt);
+    }
+
+    fn render(&self, encoder: &mut wgpu::CommandEncoder, target: &RenderTarget, clear_color: wgpu::Color) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            color_attachments: &[target.color_attachment(clear_color)],
+            depth_stencil_attachment: Some(target.depth_attachment()),
+        });
+
+        for (i, shape) in self.shapes.iter().enumerate() {
+            let i = i as u32;
+            render_pass.set_pipeline(self.render_pipelines.get(shape.index_format).unwrap());
+            render_pass.set_bind_group(0, &self.diffuse_bind_group, &[]);
+            render_pass.set_bind_group(1, &self.uniform_bind_group, &[]);
+            render_pass.set_vertex_buffers(0, &[(&shape.vertex_buffer, 0), (&self.instance_buffer, 0)]);
+            render_pass.set_index_buffer(&shape.index_buffer, 0);
+            render_pass.draw_indexed(0..shape.num_indices, 0, i..i + 1);
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use framework::shader::{create_spirv_module, ShaderError, ShaderStage};
+
+use super::Scene;
+use crate::target::RenderTarget;
+
+// The triangle from the pipeline tutorial. Its vertices live in the shader,
+// so we don't need any buffers.
+pub struct Triangle {
+    render_pipeline: wgpu::RenderPipeline,
+}
+
+impl Triangle {
+    pub fn new(device: &wgpu::Device, target: &RenderTarget) -> Result<Self, ShaderError> {
+        let vs_spirv = framework::include_spirv!("src/scene/triangle.vert");
+        let fs_spirv = framework::include_spirv!("src/scene/triangle.frag");
+        let vs_module = create_spirv_module(device, "triangle.vert", ShaderStage::Vertex, vs_spirv)?;
+        let fs_module = create_spirv_module(device, "triangle.frag", ShaderStage::Fragment, fs_spirv)?;
+
+        let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            bind_group_layouts: &[],
+        });
+
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            layout: &render_pipeline_layout,
+            vertex_stage: wgpu::ProgrammableStageDescriptor {
+                module: &vs_module,
+                entry_point: "main",
+            },
+            fragment_stage: Some(wgpu::ProgrammableStageDescriptor {
+                module: &fs_module,
+                entry_point: "main",
+            }),
+            rasterization_state: Some(wgpu::RasterizationStateDescriptor {
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: wgpu::CullMode::Back,
+                depth_bias: 0,
+                depth_bias_slope_scale: 0.0,
+                depth_bias_clamp: 0.0,
+            }),
+            primitive_topology: wgpu::PrimitiveTopology::TriangleList,
+            color_states: &[
+                wgpu::ColorStateDescriptor {
+                    format: target.format,
+                    color_blend: wgpu::BlendDescriptor::REPLACE,
+                    alpha_blend: wgpu::BlendDescriptor::REPLACE,
+                    write_mask: wgpu::ColorWrite::ALL,
+                },
+            ],
+            depth_stencil_state: Some(target.depth_stencil_state()),
+            // Only used by indexed draws, and this doesn't have an index buffer
+            index_format: wgpu::IndexFormat::Uint16,
+            vertex_buffers: &[],
+            sample_count: target.sample_count,
+            sample_mask: !0,
+            alpha_to_coverage_enabled: false,
+        });
+
+        Ok(Self { render_pipeline })
+    }
+}
+
+impl Scene for Triangle {
+    fn render(&self, encoder: &mut wgpu::CommandEncoder, target: &RenderTarget, clear_color: wgpu::Color) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            color_attachments: &[target.color_attachment(clear_color)],
+            depth_stencil_attachment: Some(target.depth_attachment()),
+        });
+
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,137 @@
+use image::RgbaImage;
+
+use crate::depth::{DepthImage, DEPTH_FORMAT};
+use crate::hdr::{self, HdrImage};
+use crate::readback::{self, ReadbackError};
+
+// A texture we can draw to in place of a swap chain frame
+pub struct RenderTarget {
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+    pub size: wgpu::Extent3d,
+    pub format: wgpu::TextureFormat,
+    pub sample_count: u32,
+    // With more than one sample we draw into this, and the gpu resolves it into `texture`.
+    // The texture is kept around so it lives as long as its view.
+    multisampled: Option<(wgpu::Texture, wgpu::TextureView)>,
+    pub depth_texture: wgpu::Texture,
+    pub depth_view: wgpu::TextureView,
+}
+
+impl RenderTarget {
+    pub fn new(device: &wgpu::Device, size: wgpu::Extent3d, format: wgpu::TextureFormat, sample_count: u32) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            size,
+            array_layer_count: 1,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT | wgpu::TextureUsage::COPY_SRC,
+        });
+        let view = texture.create_default_view();
+
+        let multisampled = if sample_count > 1 {
+            let multisampled_texture = device.create_texture(&wgpu::TextureDescriptor {
+                size,
+                array_layer_count: 1,
+                mip_level_count: 1,
+                sample_count,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT,
+            });
+            let multisampled_view = multisampled_texture.create_default_view();
+            Some((multisampled_texture, multisampled_view))
+        } else {
+            None
+        };
+
+        let depth_texture = device.create_texture(&wgpu::TextureDescriptor {
+            size,
+            array_layer_count: 1,
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format: DEPTH_FORMAT,
+            usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT | wgpu::TextureUsage::COPY_SRC,
+        });
+        let depth_view = depth_texture.create_default_view();
+
+        Self {
+            texture,
+            view,
+            size,
+            format,
+            sample_count,
+            multisampled,
+            depth_texture,
+            depth_view,
+        }
+    }
+
+    pub fn color_attachment(&self, clear_color: wgpu::Color) -> wgpu::RenderPassColorAttachmentDescriptor<'_> {
+        let (attachment, resolve_target) = match &self.multisampled {
+            Some((_, multisampled_view)) => (multisampled_view, Some(&self.view)),
+            None => (&self.view, None),
+        };
+
+        wgpu::RenderPassColorAttachmentDescriptor {
+            attachment,
+            resolve_target,
+            load_op: wgpu::LoadOp::Clear,
+            store_op: wgpu::StoreOp::Store,
+            clear_color,
+        }
+    }
+
+    pub fn depth_attachment(&self) -> wgpu::RenderPassDepthStencilAttachmentDescriptor<&wgpu::TextureView> {
+        wgpu::RenderPassDepthStencilAttachmentDescriptor {
+            attachment: &self.depth_view,
+            depth_load_op: wgpu::LoadOp::Clear,
+            depth_store_op: wgpu::StoreOp::Store,
+            clear_depth: 1.0,
+            stencil_load_op: wgpu::LoadOp::Clear,
+            stencil_store_op: wgpu::StoreOp::Store,
+            clear_stencil: 0,
+        }
+    }
+
+    // Pipelines drawing to this target need to use this so their depth format matches
+    pub fn depth_stencil_state(&self) -> wgpu::DepthStencilStateDescriptor {
+        wgpu::DepthStencilStateDescriptor {
+            format: DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil_front: wgpu::StencilStateFaceDescriptor::IGNORE,
+            stencil_back: wgpu::StencilStateFaceDescriptor::IGNORE,
+            stencil_read_mask: 0,
+            stencil_write_mask: 0,
+        }
+    }
+
+    pub fn is_hdr(&self) -> bool {
+        hdr::is_float_format(self.format)
+    }
+
+    // The texels exactly as they are stored in the texture
+    pub fn read_bytes(&self, device: &wgpu::Device, queue: &mut wgpu::Queue) -> Result<Vec<u8>, ReadbackError> {
+        readback::read_texture_bytes(device, queue, &self.texture, self.size, self.format)
+    }
+
+    pub fn read(&self, device: &wgpu::Device, queue: &mut wgpu::Queue) -> Result<RgbaImage, ReadbackError> {
+        readback::read_texture(device, queue, &self.texture, self.size, self.format)
+    }
+
+    pub fn read_hdr(&self, device: &wgpu::Device, queue: &mut wgpu::Queue) -> Result<HdrImage, ReadbackError> {
+        let data = self.read_bytes(device, queue)?;
+        Ok(HdrImage::from_bytes(self.format, self.size.width, self.size.height, &data))
+    }
+
+    // Multisampled depth can't be copied out, so this only works with a sample count of 1
+    pub fn read_depth(&self, device: &wgpu::Device, queue: &mut wgpu::Queue) -> Result<DepthImage, ReadbackError> {
+        assert_eq!(self.sample_count, 1, "can't read multisampled depth");
+        let data = readback::read_texture_bytes(device, queue, &self.depth_texture, self.size, DEPTH_FORMAT)?;
+        Ok(DepthImage::from_bytes(self.size.width, self.size.height, &data))
+    }
+}
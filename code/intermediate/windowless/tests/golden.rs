@@ -0,0 +1,88 @@
+// Renders every scene that has a reference image in golden/ and compares it
+// with `golden::compare`. Failed renders and their diffs are written to
+// target/golden/.
+//
+// wgpu 0.4 aborts the whole process when there's no adapter instead of
+// letting us skip, so the check is ignored by default. Run it with
+// check-golden.sh, or `cargo test --test golden -- --ignored`. Without a gpu,
+// point VK_ICD_FILENAMES at a software vulkan driver like lavapipe first.
+use image::RgbaImage;
+use std::path::Path;
+
+use windowless::golden::{self, Tolerance};
+use windowless::scene::SceneName;
+use windowless::target::RenderTarget;
+
+const GOLDEN_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/golden");
+const OUTPUT_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/target/golden");
+
+// The same defaults `windowless --golden` renders with
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+const CLEAR_COLOR: wgpu::Color = wgpu::Color {
+    r: 0.1,
+    g: 0.2,
+    b: 0.3,
+    a: 1.0,
+};
+const TOLERANCE: Tolerance = Tolerance {
+    channel: 2,
+    max_mismatch_ratio: 0.001,
+};
+
+fn render(device: &wgpu::Device, queue: &mut wgpu::Queue, scene: SceneName, size: wgpu::Extent3d) -> RgbaImage {
+    let target = RenderTarget::new(device, size, FORMAT, 1);
+    let scene = scene.create(device, queue, &target).unwrap();
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        todo: 0,
+    });
+    scene.render(&mut encoder, &target, CLEAR_COLOR);
+    queue.submit(&[encoder.finish()]);
+
+    target.read(device, queue).unwrap()
+}
+
+#[test]
+#[ignore]
+fn scenes_match_their_references() {
+    let adapter = wgpu::Adapter::request(&wgpu::RequestAdapterOptions {
+        backends: wgpu::BackendBit::PRIMARY,
+        ..Default::default()
+    }).expect("no adapter found");
+    let (device, mut queue) = adapter.request_device(&Default::default());
+
+    let mut failures = Vec::new();
+    for &scene in SceneName::ALL {
+        let reference_path = Path::new(GOLDEN_DIR).join(format!("{}.png", scene.name()));
+        let reference = match image::open(&reference_path) {
+            Ok(reference) => reference.to_rgba(),
+            Err(_) => {
+                eprintln!("{}: no reference image, skipping (create one with --golden --bless)", scene.name());
+                continue;
+            }
+        };
+
+        let size = wgpu::Extent3d {
+            width: reference.width(),
+            height: reference.height(),
+            depth: 1,
+        };
+        let image = render(&device, &mut queue, scene, size);
+        let comparison = golden::compare(&image, &reference, &TOLERANCE).unwrap();
+        if !comparison.passed(&TOLERANCE) {
+            let output_path = Path::new(OUTPUT_DIR).join(format!("{}.png", scene.name()));
+            std::fs::create_dir_all(OUTPUT_DIR).unwrap();
+            image.save(&output_path).unwrap();
+            comparison.diff.save(golden::diff_path(&output_path)).unwrap();
+            failures.push(format!(
+                "{}: {} of {} pixels differ, wrote {:?}",
+                scene.name(),
+                comparison.mismatched_pixels,
+                comparison.total_pixels,
+                output_path,
+            ));
+        }
+    }
+
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}